@@ -0,0 +1,405 @@
+use crate::crypto;
+use crate::database::{DbPool, SqlitePool};
+use crate::error::{AppError, ErrorCategory};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager};
+
+/// URI scheme the webview loads attachments from. Use
+/// `convertFileSrc(id, 'attachment')` in the frontend to build the URL, since
+/// Windows serves custom schemes as `http://attachment.localhost/<id>`.
+pub const URI_SCHEME: &str = "attachment";
+const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+/// Temporary files younger than this may belong to a write still in progress
+const TEMP_FILE_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Adding an attachment holds this shared from writing the blob until its row
+/// exists; garbage collection holds it exclusively, so it can never see a
+/// blob that is about to be referenced as unreferenced.
+static BLOB_STORE: tokio::sync::RwLock<()> = tokio::sync::RwLock::const_new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub note_id: String,
+    /// SHA-256 of the file contents, which is also its name in the blob store
+    pub hash: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub created_at: String,
+}
+
+/// Root of the content-addressed blob store, created if missing
+pub fn blob_root(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let root = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Storage(e.to_string()))?
+        .join("attachments");
+
+    std::fs::create_dir_all(&root)
+        .map_err(|e| AppError::Storage(format!("Failed to create attachment store: {}", e)))?;
+
+    Ok(root)
+}
+
+/// Blobs are sharded by the first two hex digits to keep directories small
+fn blob_path(root: &Path, hash: &str) -> PathBuf {
+    root.join(&hash[..2]).join(hash)
+}
+
+fn mime_type_for(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Write a blob via a temporary file so a crash never leaves a truncated
+/// blob under a valid hash
+fn write_blob(path: &Path, data: Vec<u8>) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, crypto::encrypt_blob(data)?)
+        .and_then(|_| std::fs::rename(&temp_path, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            AppError::Storage(format!("Failed to store attachment: {}", e))
+        })
+}
+
+fn read_failed(source: &Path, e: std::io::Error) -> AppError {
+    AppError::Storage(format!("Failed to read {}: {}", source.display(), e))
+}
+
+/// Copy a file into the blob store and attach it to a note.
+///
+/// Identical files share one blob; only the `attachments` row is new.
+pub async fn add_attachment(
+    pool: &SqlitePool,
+    root: &Path,
+    note_id: &str,
+    source: &Path,
+) -> Result<Attachment, AppError> {
+    let note_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ? AND deleted_at IS NULL)",
+    )
+    .bind(note_id)
+    .fetch_one(pool)
+    .await?;
+
+    if !note_exists {
+        return Err(AppError::NotFound(format!("Note not found: {}", note_id)));
+    }
+
+    let size = tokio::fs::metadata(source)
+        .await
+        .map_err(|e| read_failed(source, e))?
+        .len();
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "Attachments are limited to {} MB",
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let data = tokio::fs::read(source)
+        .await
+        .map_err(|e| read_failed(source, e))?;
+    let size_bytes = data.len() as i64;
+    let (hash, data) =
+        tokio::task::spawn_blocking(move || (format!("{:x}", Sha256::digest(&data)), data))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let _store = BLOB_STORE.read().await;
+    let path = blob_path(root, &hash);
+    if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        tokio::task::spawn_blocking(move || write_blob(&path, data))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+    }
+
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "attachment".to_string());
+
+    let attachment = Attachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        note_id: note_id.to_string(),
+        mime_type: mime_type_for(&file_name).to_string(),
+        hash,
+        file_name,
+        size_bytes,
+        created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO attachments (id, note_id, hash, file_name, mime_type, size_bytes, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&attachment.id)
+    .bind(&attachment.note_id)
+    .bind(&attachment.hash)
+    .bind(&attachment.file_name)
+    .bind(&attachment.mime_type)
+    .bind(attachment.size_bytes)
+    .bind(&attachment.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(attachment)
+}
+
+/// Attachments of a note, oldest first
+pub async fn list_attachments(
+    pool: &SqlitePool,
+    note_id: &str,
+) -> Result<Vec<Attachment>, AppError> {
+    sqlx::query_as::<_, Attachment>(
+        r#"
+        SELECT id, note_id, hash, file_name, mime_type, size_bytes, created_at
+        FROM attachments
+        WHERE note_id = ?
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(note_id)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Detach a file from its note. The blob stays until `collect_garbage`, as a
+/// backup may still refer to it.
+pub async fn remove_attachment(pool: &SqlitePool, id: &str) -> Result<(), AppError> {
+    let removed = sqlx::query("DELETE FROM attachments WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if removed.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Attachment not found: {}", id)));
+    }
+
+    Ok(())
+}
+
+/// Every file currently in the blob store
+fn stored_blobs(root: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut blobs = Vec::new();
+    let shards = std::fs::read_dir(root)?;
+
+    for shard in shards
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if !shard.is_dir() {
+            continue;
+        }
+        let files = std::fs::read_dir(&shard)?;
+        blobs.extend(
+            files
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file()),
+        );
+    }
+
+    Ok(blobs)
+}
+
+/// Whether a file was last modified longer than `TEMP_FILE_GRACE` ago
+fn is_stale(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age >= TEMP_FILE_GRACE)
+}
+
+/// Blob hashes referenced by a backup file, which only copies the database
+async fn referenced_in_backup(path: &Path) -> Result<HashSet<String>, AppError> {
+    let unreadable =
+        |e: sqlx::Error| AppError::Storage(format!("Failed to read {}: {}", path.display(), e));
+
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{}?mode=ro", path.display()))
+        .await
+        .map_err(unreadable)?;
+
+    // Backups taken before attachments existed have no table to read
+    let has_table: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'attachments')",
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(unreadable)?;
+
+    let hashes = if has_table {
+        sqlx::query_scalar::<_, String>("SELECT DISTINCT hash FROM attachments")
+            .fetch_all(&pool)
+            .await
+            .map_err(unreadable)?
+    } else {
+        Vec::new()
+    };
+
+    pool.close().await;
+    Ok(hashes.into_iter().collect())
+}
+
+/// Delete blobs no attachment refers to any more (e.g. after notes were
+/// purged from the trash), returning how many were removed.
+///
+/// Backups hold attachment rows but not the files, so blobs referenced by any
+/// of `backups` are kept to let a restored backup still show its attachments.
+/// A backup that cannot be read stops the collection rather than risk it.
+pub async fn collect_garbage(
+    pool: &SqlitePool,
+    root: &Path,
+    backups: &[PathBuf],
+) -> Result<u64, AppError> {
+    let _store = BLOB_STORE.write().await;
+    let mut referenced: HashSet<String> =
+        sqlx::query_scalar::<_, String>("SELECT DISTINCT hash FROM attachments")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+    for backup in backups {
+        referenced.extend(referenced_in_backup(backup).await?);
+    }
+
+    let mut removed = 0;
+    for path in stored_blobs(root)? {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Leftover temporary files from an interrupted write go as well,
+        // once they are too old to belong to one still running
+        if path.extension().is_some_and(|e| e == "tmp") && !is_stale(&path) {
+            continue;
+        }
+
+        if !referenced.contains(&name) && std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Encrypt blobs stored before encryption was enabled, returning how many were rewritten
+pub fn encrypt_existing(root: &Path) -> Result<u64, AppError> {
+    let mut encrypted = 0;
+    for path in stored_blobs(root)? {
+        let data = std::fs::read(&path)?;
+        if crypto::is_encrypted_blob(&data) || path.extension().is_some() {
+            continue;
+        }
+        write_blob(&path, data)?;
+        encrypted += 1;
+    }
+    Ok(encrypted)
+}
+
+/// Look up an attachment by id and return its MIME type and decrypted bytes
+async fn load_blob(app_handle: &AppHandle, id: &str) -> Result<(String, Vec<u8>), AppError> {
+    let pool = app_handle
+        .state::<DbPool>()
+        .wait_ready()
+        .await?
+        .clone()
+        .ok_or(AppError::DatabaseLocked)?;
+
+    let row: Option<(String, String)> =
+        sqlx::query_as("SELECT hash, mime_type FROM attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await?;
+    let (hash, mime_type) =
+        row.ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", id)))?;
+
+    let root = blob_root(app_handle)?;
+    let data = std::fs::read(blob_path(&root, &hash))
+        .map_err(|_| AppError::NotFound("Attachment file is missing".to_string()))?;
+    let data = crypto::decrypt_blob(data)?;
+
+    Ok((mime_type, data))
+}
+
+/// HTTP status for a failed attachment request
+fn error_status(error: &AppError) -> StatusCode {
+    match error.category() {
+        ErrorCategory::NotFound => StatusCode::NOT_FOUND,
+        ErrorCategory::Auth => StatusCode::FORBIDDEN,
+        _ if error.retryable() => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handler for the `attachment://` protocol.
+///
+/// Only attachment ids are accepted, never paths, so the webview can show
+/// attachments without gaining any access to the filesystem.
+pub async fn serve(app_handle: &AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let id = request.uri().path().trim_start_matches('/');
+
+    let response = match load_blob(app_handle, id).await {
+        Ok((mime_type, data)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, mime_type)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            // SVGs may carry scripts; never let an attachment run anything
+            .header(
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; style-src 'unsafe-inline'",
+            )
+            .body(data),
+        Err(error) => Response::builder()
+            .status(error_status(&error))
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(error.to_string().into_bytes()),
+    };
+
+    response.unwrap_or_else(|_| {
+        let mut fallback = Response::new(Vec::new());
+        *fallback.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        fallback
+    })
+}
@@ -0,0 +1,312 @@
+use crate::crypto;
+use crate::database::{self, DbPool, SqlitePool};
+use crate::error::AppError;
+use crate::settings::{self, DataSettings};
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const BACKUP_PREFIX: &str = "helper-backup-";
+const BACKUP_SUFFIX: &str = ".db";
+/// Millisecond resolution, so a manual backup and a scheduled one (or the
+/// safety copy taken before a restore) never share a file name
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+/// Also accepts names from before milliseconds were added
+const TIMESTAMP_PARSE_FORMAT: &str = "%Y%m%d-%H%M%S%.f";
+/// How often the scheduler wakes up to check whether a backup is due
+const SCHEDULER_TICK_SECS: u64 = 15 * 60;
+
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+/// Resolve (and create) the directory backups are written to
+pub fn backup_dir(app_handle: &AppHandle, config: &DataSettings) -> Result<PathBuf, AppError> {
+    let dir = match &config.backup_directory {
+        Some(dir) => PathBuf::from(dir),
+        None => app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| AppError::Storage(e.to_string()))?
+            .join("backups"),
+    };
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::Storage(format!("Failed to create backup directory: {}", e)))?;
+
+    Ok(dir)
+}
+
+fn parse_backup_name(file_name: &str) -> Option<DateTime<Utc>> {
+    let stamp = file_name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)?;
+    NaiveDateTime::parse_from_str(stamp, TIMESTAMP_PARSE_FORMAT)
+        .ok()
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>, AppError> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| AppError::Storage(format!("Failed to read backups: {}", e)))?;
+
+    let mut backups: Vec<(DateTime<Utc>, BackupInfo)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let created_at = parse_backup_name(&file_name)?;
+            let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            Some((
+                created_at,
+                BackupInfo {
+                    file_name,
+                    created_at: created_at.to_rfc3339(),
+                    size_bytes,
+                },
+            ))
+        })
+        .collect();
+
+    backups.sort_by_key(|(created_at, _)| std::cmp::Reverse(*created_at));
+    Ok(backups.into_iter().map(|(_, info)| info).collect())
+}
+
+/// Run SQLite's integrity check against a backup file without modifying it
+pub async fn verify_backup(path: &Path) -> Result<(), AppError> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{}?mode=ro", path.display()))
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to open backup: {}", e)))?;
+
+    let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Integrity check failed: {}", e)))?;
+
+    pool.close().await;
+
+    if results.len() == 1 && results[0] == "ok" {
+        Ok(())
+    } else {
+        Err(AppError::Storage(format!(
+            "Backup is corrupt: {}",
+            results.join("; ")
+        )))
+    }
+}
+
+/// Write a consistent, verified copy of the live database into `dir`
+pub async fn create_backup(pool: &SqlitePool, dir: &Path) -> Result<BackupInfo, AppError> {
+    let now = Utc::now();
+    let file_name = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        now.format(TIMESTAMP_FORMAT),
+        BACKUP_SUFFIX
+    );
+    let path = dir.join(&file_name);
+
+    // VACUUM INTO is safe while other connections are reading and writing
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Backup failed: {}", e)))?;
+
+    if let Err(e) = verify_backup(&path).await {
+        let _ = std::fs::remove_file(&path);
+        return Err(e);
+    }
+
+    let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+    Ok(BackupInfo {
+        file_name,
+        created_at: now.to_rfc3339(),
+        size_bytes,
+    })
+}
+
+/// Delete backups outside the retention policy: the newest backup of each of
+/// the last `backup_retention` days and of each of the last
+/// `backup_weekly_retention` ISO weeks are kept. Returns how many files were removed.
+pub fn rotate_backups(dir: &Path, config: &DataSettings) -> Result<usize, AppError> {
+    let backups = list_backups(dir)?;
+    let mut keep: HashSet<String> = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    for backup in &backups {
+        let created = match DateTime::parse_from_rfc3339(&backup.created_at) {
+            Ok(ts) => ts,
+            Err(_) => continue,
+        };
+
+        if days.len() < config.backup_retention as usize && days.insert(created.date_naive()) {
+            keep.insert(backup.file_name.clone());
+        }
+
+        let week = created.iso_week();
+        if weeks.len() < config.backup_weekly_retention as usize
+            && weeks.insert((week.year(), week.week()))
+        {
+            keep.insert(backup.file_name.clone());
+        }
+    }
+
+    let mut removed = 0;
+    for backup in backups.iter().filter(|b| !keep.contains(&b.file_name)) {
+        if std::fs::remove_file(dir.join(&backup.file_name)).is_ok() {
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Take a backup if the newest one is older than the configured interval
+async fn run_scheduled_backup(app_handle: &AppHandle) -> Result<(), AppError> {
+    // Clone the pool so commands are not blocked while the backup runs;
+    // nothing happens while the database is locked or being restored.
+    let pool = match app_handle.state::<DbPool>().0.lock().await.clone() {
+        Some(pool) => pool,
+        None => return Ok(()),
+    };
+
+    let config = settings::load(&pool).await?.data;
+    if !config.backup_enabled {
+        return Ok(());
+    }
+
+    let dir = backup_dir(app_handle, &config)?;
+    let due = match list_backups(&dir)?.first() {
+        Some(latest) => DateTime::parse_from_rfc3339(&latest.created_at)
+            .map(|ts| {
+                Utc::now() - ts.with_timezone(&Utc)
+                    >= chrono::Duration::hours(config.backup_frequency.hours() as i64)
+            })
+            .unwrap_or(true),
+        None => true,
+    };
+
+    if due {
+        create_backup(&pool, &dir).await?;
+        rotate_backups(&dir, &config)?;
+    }
+
+    Ok(())
+}
+
+/// Start the background task that keeps scheduled backups up to date. Only
+/// the first call does anything, so retrying startup cannot start a second one.
+pub fn start_scheduler(app_handle: AppHandle) {
+    if SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(SCHEDULER_TICK_SECS));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = run_scheduled_backup(&app_handle).await {
+                eprintln!("Scheduled backup failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Replace the database file with `source` and remove any stale WAL files
+fn replace_database_file(source: &Path, db_path: &Path) -> Result<(), AppError> {
+    for suffix in ["-wal", "-shm"] {
+        let mut side_file = db_path.as_os_str().to_owned();
+        side_file.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(side_file));
+    }
+
+    std::fs::copy(source, db_path)
+        .map(|_| ())
+        .map_err(|e| AppError::Storage(format!("Failed to copy backup into place: {}", e)))
+}
+
+/// Open a restored database file. `VACUUM INTO` copies may have renumbered
+/// the implicit rowids of `notes` that the search index refers to, so it is
+/// rebuilt before use.
+async fn open_restored(db_path: &Path) -> Result<SqlitePool, String> {
+    let pool = database::open(db_path).await.map_err(|e| e.to_string())?;
+    if let Err(e) = sqlx::query("INSERT INTO notes_fts(notes_fts) VALUES ('rebuild')")
+        .execute(&pool)
+        .await
+    {
+        pool.close().await;
+        return Err(e.to_string());
+    }
+    Ok(pool)
+}
+
+/// Restore a backup over the live database while the app keeps running.
+///
+/// Holding the `DbPool` lock for the whole swap means every command simply
+/// waits until the restored database is open. The current state is backed
+/// up first and put back if the restored file cannot be opened.
+pub async fn restore_backup(app_handle: &AppHandle, file_name: &str) -> Result<(), AppError> {
+    let db = app_handle.state::<DbPool>();
+    let mut guard = db.wait_ready().await?;
+    let pool = guard.as_ref().ok_or(AppError::DatabaseLocked)?.clone();
+
+    let config = settings::load(&pool).await?.data;
+    let dir = backup_dir(app_handle, &config)?;
+
+    if !list_backups(&dir)?.iter().any(|b| b.file_name == file_name) {
+        return Err(AppError::NotFound(format!(
+            "Backup not found: {}",
+            file_name
+        )));
+    }
+    let backup_path = dir.join(file_name);
+    verify_backup(&backup_path).await?;
+
+    let safety_copy = create_backup(&pool, &dir).await?;
+    let db_path =
+        database::database_path(app_handle).map_err(|e| AppError::Storage(e.to_string()))?;
+
+    guard.take();
+    pool.close().await;
+
+    replace_database_file(&backup_path, &db_path)?;
+    let restored = match open_restored(&db_path).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            let message = format!("Restored database could not be opened: {}", e);
+            replace_database_file(&dir.join(&safety_copy.file_name), &db_path)?;
+            let original = open_restored(&db_path).await.map_err(AppError::Storage)?;
+            *guard = Some(original);
+            return Err(AppError::Storage(message));
+        }
+    };
+
+    // The restored file may be encrypted with a different passphrase
+    crypto::lock();
+    if crypto::is_configured(&restored).await? {
+        *app_handle.state::<crypto::LockedPool>().0.lock().await = Some(restored);
+        db.set_status(database::DatabaseStatus::Locked);
+        let _ = app_handle.emit("database-locked", ());
+    } else {
+        *guard = Some(restored);
+    }
+
+    let _ = app_handle.emit("database-restored", file_name);
+
+    Ok(())
+}
@@ -1,11 +1,31 @@
 use serde::{Deserialize, Serialize};
-use tauri::State;
-use crate::database::DbPool;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, State};
+use crate::attachments;
+use crate::backup;
+use crate::crypto;
+use crate::database::{self, DatabaseStatus, DbPool};
+use crate::embeddings;
+use crate::error::AppError;
+use crate::export;
+use crate::import;
+use crate::journal_chat;
+use crate::links;
+use crate::merge;
 use crate::natlangchain;
+use crate::note_context;
+use crate::notes;
 use crate::ollama;
+use crate::revisions;
+use crate::search;
+use crate::settings;
+use crate::stats;
+use crate::tags;
+use crate::templates;
+use crate::trash;
 use crate::weather;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Note {
     pub id: String,
@@ -15,6 +35,14 @@ pub struct Note {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    /// Tag names on the note. On update, `None` leaves existing tags untouched.
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub tags: Option<Vec<String>>,
+    /// Weather and time context captured when the note was written
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub context: Option<weather::JournalContext>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,44 +59,134 @@ pub struct OllamaStatus {
     pub error: Option<String>,
 }
 
+/// Progress of a streamed chat reply, sent over the command's channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum ChatStreamEvent {
+    /// The next piece of generated text
+    Chunk { content: String },
+    /// The reply is complete
+    Done {
+        message: ChatMessage,
+        stats: ollama::ChatStats,
+    },
+}
+
+/// How a chat request ended. Cancelling is not an error, so it has its own
+/// outcome instead.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "camelCase")]
+pub enum ChatOutcome {
+    Completed { message: ChatMessage },
+    Cancelled,
+}
+
+impl From<Option<ChatMessage>> for ChatOutcome {
+    fn from(message: Option<ChatMessage>) -> Self {
+        match message {
+            Some(message) => ChatOutcome::Completed { message },
+            None => ChatOutcome::Cancelled,
+        }
+    }
+}
+
+/// An answer about the journal and the entries it was based on
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalAnswer {
+    #[serde(flatten)]
+    pub outcome: ChatOutcome,
+    pub sources: Vec<journal_chat::JournalSource>,
+}
+
 /// Get all notes for a specific date
 #[tauri::command]
 pub async fn get_notes_for_date(
     date: String,
     db: State<'_, DbPool>,
-) -> Result<Vec<Note>, String> {
-    let pool = db.0.lock().await;
+) -> Result<Vec<Note>, AppError> {
+    let pool = db.wait_ready().await?;
 
     if let Some(pool) = pool.as_ref() {
-        let notes = sqlx::query_as!(
-            Note,
+        let mut notes = sqlx::query_as::<_, Note>(
             r#"
             SELECT id, date, title, content, created_at, updated_at, deleted_at
             FROM notes
             WHERE date = ? AND deleted_at IS NULL
             ORDER BY created_at DESC
             "#,
-            date
         )
+        .bind(&date)
         .fetch_all(pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
+
+        notes::hydrate(pool, &mut notes).await?;
 
         Ok(notes)
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Get notes in a date range, one page at a time
+#[tauri::command]
+pub async fn get_notes_for_range(
+    start: String,
+    end: String,
+    cursor: Option<notes::NoteCursor>,
+    limit: Option<u32>,
+    preview_chars: Option<u32>,
+    db: State<'_, DbPool>,
+) -> Result<notes::NotePage, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        notes::notes_for_range(pool, &start, &end, cursor.as_ref(), limit, preview_chars).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Per-day activity for a month (or a whole year) to drive the calendar view
+#[tauri::command]
+pub async fn get_calendar_summary(
+    year: i32,
+    month: Option<u32>,
+    db: State<'_, DbPool>,
+) -> Result<Vec<notes::DaySummary>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        notes::calendar_summary(pool, year, month).await
+    } else {
+        Err(AppError::DatabaseLocked)
     }
 }
 
-/// Create a new note
+/// Create a new note. A note created without content is filled in from
+/// the default template for its weekday, if there is one.
 #[tauri::command]
 pub async fn create_note(
-    note: Note,
+    mut note: Note,
     db: State<'_, DbPool>,
-) -> Result<Note, String> {
-    let pool = db.0.lock().await;
+) -> Result<Note, AppError> {
+    let pool = db.wait_ready().await?;
 
     if let Some(pool) = pool.as_ref() {
+        // A blank note starts from the default template for its weekday
+        if note.content.trim().is_empty() {
+            if let Some(content) =
+                templates::render_default(pool, &note.date, note.context.as_ref()).await?
+            {
+                note.content = content;
+            }
+        }
+
+        let stored_title = crypto::encrypt_optional(&note.title)?;
+        let stored_content = crypto::encrypt_text(&note.content)?;
+
+        let mut tx = pool.begin().await?;
+
         sqlx::query!(
             r#"
             INSERT INTO notes (id, date, title, content, created_at, updated_at)
@@ -76,48 +194,104 @@ pub async fn create_note(
             "#,
             note.id,
             note.date,
-            note.title,
-            note.content,
+            stored_title,
+            stored_content,
             note.created_at,
             note.updated_at
         )
-        .execute(pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .execute(&mut *tx)
+        .await?;
+
+        let note_tags = note.tags.take().unwrap_or_default();
+        note.tags = Some(tags::set_note_tags(&mut tx, &note.id, &note_tags).await?);
+
+        links::index_note(&mut tx, &note.id, &note.content).await?;
+        links::title_changed(&mut tx, &note.id, None, note.title.as_deref()).await?;
+
+        if let Some(context) = &note.context {
+            note_context::save_context(&mut tx, &note.id, context).await?;
+        }
+
+        tx.commit().await?;
+        stats::invalidate();
+        embeddings::schedule();
 
         Ok(note)
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::DatabaseLocked)
     }
 }
 
-/// Update an existing note
+/// Update an existing note.
+///
+/// When `expected_updated_at` is given the update only goes through if the
+/// stored note still has that `updated_at`; otherwise it fails with a
+/// conflict carrying the saved version, so concurrent edits are never
+/// silently overwritten.
 #[tauri::command]
 pub async fn update_note(
-    note: Note,
+    mut note: Note,
+    expected_updated_at: Option<String>,
     db: State<'_, DbPool>,
-) -> Result<Note, String> {
-    let pool = db.0.lock().await;
+) -> Result<Note, AppError> {
+    let pool = db.wait_ready().await?;
 
     if let Some(pool) = pool.as_ref() {
-        sqlx::query!(
+        let stored_title = crypto::encrypt_optional(&note.title)?;
+        let stored_content = crypto::encrypt_text(&note.content)?;
+
+        let mut tx = pool.begin().await?;
+
+        // Keep the previous version around before overwriting it
+        revisions::record_revision(&mut tx, &note.id, &note.title, &note.content, true).await?;
+        let previous_title = links::stored_title(&mut tx, &note.id).await?;
+
+        // Checking the version in the UPDATE itself also catches writers
+        // outside this process, e.g. a sync tool on the same file
+        let updated = sqlx::query!(
             r#"
             UPDATE notes
             SET title = ?, content = ?, updated_at = ?
-            WHERE id = ?
+            WHERE id = ? AND updated_at = COALESCE(?, updated_at)
             "#,
-            note.title,
-            note.content,
+            stored_title,
+            stored_content,
             note.updated_at,
-            note.id
+            note.id,
+            expected_updated_at
         )
-        .execute(pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            drop(tx);
+            return match notes::get_note(pool, &note.id).await? {
+                Some(current) => Err(AppError::Conflict(Box::new(current))),
+                None => Err(AppError::NotFound(format!("Note not found: {}", note.id))),
+            };
+        }
+
+        if let Some(note_tags) = note.tags.take() {
+            note.tags = Some(tags::set_note_tags(&mut tx, &note.id, &note_tags).await?);
+        }
+
+        // Other notes linking to the old title are rewritten to the new one
+        links::index_note(&mut tx, &note.id, &note.content).await?;
+        links::title_changed(
+            &mut tx,
+            &note.id,
+            previous_title.as_deref(),
+            note.title.as_deref(),
+        )
+        .await?;
+
+        tx.commit().await?;
+        stats::invalidate();
+        embeddings::schedule();
 
         Ok(note)
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::DatabaseLocked)
     }
 }
 
@@ -127,8 +301,8 @@ pub async fn delete_note(
     id: String,
     deleted_at: String,
     db: State<'_, DbPool>,
-) -> Result<(), String> {
-    let pool = db.0.lock().await;
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
 
     if let Some(pool) = pool.as_ref() {
         sqlx::query!(
@@ -141,41 +315,758 @@ pub async fn delete_note(
             id
         )
         .execute(pool)
+        .await?;
+
+        stats::invalidate();
+
+        Ok(())
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// List saved revisions of a note, newest first
+#[tauri::command]
+pub async fn list_note_revisions(
+    note_id: String,
+    db: State<'_, DbPool>,
+) -> Result<Vec<revisions::NoteRevision>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        revisions::list_revisions(pool, &note_id).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Line-level diff between two revisions (omit a side to compare against the current note)
+#[tauri::command]
+pub async fn diff_note_revisions(
+    note_id: String,
+    from_revision_id: Option<String>,
+    to_revision_id: Option<String>,
+    db: State<'_, DbPool>,
+) -> Result<revisions::RevisionDiff, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        revisions::diff_revisions(
+            pool,
+            &note_id,
+            from_revision_id.as_deref(),
+            to_revision_id.as_deref(),
+        )
         .await
-        .map_err(|e| e.to_string())?;
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Replace a note's content with one of its earlier revisions
+#[tauri::command]
+pub async fn restore_note_revision(
+    note_id: String,
+    revision_id: String,
+    db: State<'_, DbPool>,
+) -> Result<Note, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let note = revisions::restore_revision(pool, &note_id, &revision_id).await?;
+        stats::invalidate();
+        embeddings::schedule();
+        Ok(note)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Three-way merge of the caller's edit with the saved version of a note,
+/// both derived from `base`; used to resolve an `update_note` conflict
+#[tauri::command]
+pub fn merge_note_content(base: String, yours: String, saved: String) -> merge::MergeResult {
+    merge::merge(&base, &yours, &saved)
+}
+
+/// Full-text search across all notes
+#[tauri::command]
+pub async fn search_notes(
+    query: String,
+    options: Option<search::SearchOptions>,
+    db: State<'_, DbPool>,
+) -> Result<search::SearchResults, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        search::search_notes(pool, &query, &options.unwrap_or_default()).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Notes closest in meaning to `query`, using the embeddings kept up to date
+/// in the background
+#[tauri::command]
+pub async fn semantic_search(
+    query: String,
+    limit: Option<u32>,
+    db: State<'_, DbPool>,
+) -> Result<embeddings::SemanticResults, AppError> {
+    // Embedding the query can take a while; don't hold up other commands
+    let pool = db
+        .wait_ready()
+        .await?
+        .clone()
+        .ok_or(AppError::DatabaseLocked)?;
+
+    embeddings::semantic_search(&pool, &query, limit).await
+}
+
+/// Attach (or replace) the journal context snapshot of a note
+#[tauri::command]
+pub async fn save_note_context(
+    note_id: String,
+    context: weather::JournalContext,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let mut conn = pool.acquire().await?;
+        note_context::save_context(&mut conn, &note_id, &context).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Find notes by the weather and time they were written in
+#[tauri::command]
+pub async fn get_notes_by_context(
+    filter: note_context::ContextFilter,
+    db: State<'_, DbPool>,
+) -> Result<Vec<Note>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        note_context::notes_by_context(pool, &filter).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// All tags with their note counts
+#[tauri::command]
+pub async fn get_tags(
+    db: State<'_, DbPool>,
+) -> Result<Vec<tags::TagCount>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        tags::list_tags(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Replace the tags on a single note
+#[tauri::command]
+pub async fn set_note_tags(
+    note_id: String,
+    tags: Vec<String>,
+    db: State<'_, DbPool>,
+) -> Result<Vec<String>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let mut tx = pool.begin().await?;
+        let saved = tags::set_note_tags(&mut tx, &note_id, &tags).await?;
+        tx.commit().await?;
+        Ok(saved)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Rename a tag across all notes (merges if the new name already exists)
+#[tauri::command]
+pub async fn rename_tag(
+    from: String,
+    to: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        tags::rename_tag(pool, &from, &to).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Merge several tags into one across all notes
+#[tauri::command]
+pub async fn merge_tags(
+    sources: Vec<String>,
+    target: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        tags::merge_tags(pool, &sources, &target).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Remove a tag from every note
+#[tauri::command]
+pub async fn delete_tag(
+    name: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        tags::delete_tag(pool, &name).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Notes tagged with all (`match_all`) or any of the given tags
+#[tauri::command]
+pub async fn get_notes_by_tag(
+    tags: Vec<String>,
+    match_all: Option<bool>,
+    db: State<'_, DbPool>,
+) -> Result<Vec<Note>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        tags::notes_by_tag(pool, &tags, match_all.unwrap_or(false)).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Links from a note to other notes and days, in the order they appear
+#[tauri::command]
+pub async fn get_outgoing_links(
+    note_id: String,
+    db: State<'_, DbPool>,
+) -> Result<Vec<links::NoteLink>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        links::outgoing_links(pool, &note_id).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Notes linking to a note (by its title) or to a day (`[[YYYY-MM-DD]]`)
+#[tauri::command]
+pub async fn get_backlinks(
+    note_id: Option<String>,
+    date: Option<String>,
+    db: State<'_, DbPool>,
+) -> Result<Vec<Note>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        links::backlinks(pool, note_id.as_deref(), date.as_deref()).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Link targets that no note has as its title yet
+#[tauri::command]
+pub async fn get_unresolved_links(
+    db: State<'_, DbPool>,
+) -> Result<Vec<links::UnresolvedLink>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        links::unresolved_links(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// List notes currently in the trash
+#[tauri::command]
+pub async fn list_trashed_notes(
+    db: State<'_, DbPool>,
+) -> Result<Vec<Note>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        trash::list_trashed(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Restore one or more notes from the trash
+#[tauri::command]
+pub async fn restore_notes(
+    ids: Vec<String>,
+    db: State<'_, DbPool>,
+) -> Result<u64, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let restored = trash::restore(pool, &ids).await?;
+        stats::invalidate();
+        embeddings::schedule();
+        Ok(restored)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Permanently delete one or more trashed notes
+#[tauri::command]
+pub async fn purge_notes(
+    ids: Vec<String>,
+    db: State<'_, DbPool>,
+) -> Result<u64, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        trash::purge(pool, &ids).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Permanently delete every note in the trash
+#[tauri::command]
+pub async fn empty_trash(
+    db: State<'_, DbPool>,
+) -> Result<u64, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        trash::empty(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Word counts, streaks and activity patterns across all notes
+#[tauri::command]
+pub async fn get_writing_stats(
+    db: State<'_, DbPool>,
+) -> Result<stats::WritingStats, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        stats::writing_stats(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// All note templates with the weekdays they are the default for
+#[tauri::command]
+pub async fn list_templates(
+    db: State<'_, DbPool>,
+) -> Result<Vec<templates::Template>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        templates::list_templates(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Create a template; `default_weekdays` are lowercase weekday names
+#[tauri::command]
+pub async fn create_template(
+    name: String,
+    content: String,
+    default_weekdays: Vec<String>,
+    db: State<'_, DbPool>,
+) -> Result<templates::Template, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        templates::create_template(pool, &name, &content, &default_weekdays).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Save changes to a template
+#[tauri::command]
+pub async fn update_template(
+    template: templates::Template,
+    db: State<'_, DbPool>,
+) -> Result<templates::Template, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        templates::update_template(pool, &template).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Delete a template
+#[tauri::command]
+pub async fn delete_template(
+    id: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        templates::delete_template(pool, &id).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Render a template for a note on `date`. Without a `context` the weather
+/// is looked up, so the database is not held while waiting for it.
+#[tauri::command]
+pub async fn apply_template(
+    id: String,
+    date: String,
+    context: Option<weather::JournalContext>,
+    db: State<'_, DbPool>,
+) -> Result<String, AppError> {
+    let pool = db
+        .wait_ready()
+        .await?
+        .clone()
+        .ok_or(AppError::DatabaseLocked)?;
+
+    templates::apply_template(&pool, &id, &date, context).await
+}
 
+/// Whether encryption is enabled and whether the database is still locked
+#[tauri::command]
+pub async fn get_encryption_status(
+    db: State<'_, DbPool>,
+    locked: State<'_, crypto::LockedPool>,
+) -> Result<crypto::EncryptionStatus, AppError> {
+    db.wait_until_open().await?;
+
+    if locked.0.lock().await.is_some() {
+        return Ok(crypto::EncryptionStatus {
+            enabled: true,
+            locked: true,
+        });
+    }
+
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        Ok(crypto::EncryptionStatus {
+            enabled: crypto::is_configured(pool).await?,
+            locked: false,
+        })
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Unlock an encrypted database with the user's passphrase
+#[tauri::command]
+pub async fn unlock_database(
+    passphrase: String,
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+    locked: State<'_, crypto::LockedPool>,
+) -> Result<(), AppError> {
+    let mut locked_pool = locked.0.lock().await;
+
+    if let Some(pool) = locked_pool.as_ref() {
+        crypto::unlock(pool, &passphrase).await?;
+        if let Err(e) = links::rebuild_if_needed(pool).await {
+            eprintln!("Failed to index note links: {}", e);
+        }
+        *db.0.lock().await = locked_pool.take();
+        db.set_status(DatabaseStatus::Ready);
+        let _ = app_handle.emit("database-ready", ());
         Ok(())
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::InvalidInput("Database is not locked".to_string()))
     }
 }
 
+/// Encrypt all existing notes and protect them with a passphrase
+#[tauri::command]
+pub async fn enable_encryption(
+    passphrase: String,
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        crypto::enable(pool, &passphrase).await?;
+        attachments::encrypt_existing(&attachments::blob_root(&app_handle)?)?;
+        embeddings::schedule();
+        Ok(())
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Change the passphrase protecting an encrypted database
+#[tauri::command]
+pub async fn change_encryption_passphrase(
+    current_passphrase: String,
+    new_passphrase: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        crypto::change_passphrase(pool, &current_passphrase, &new_passphrase).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Get the app settings, with defaults for anything never saved
+#[tauri::command]
+pub async fn get_settings(
+    db: State<'_, DbPool>,
+) -> Result<settings::Settings, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        settings::load(pool).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Validate and save the complete app settings, returning what was stored
+#[tauri::command]
+pub async fn update_settings(
+    settings: settings::Settings,
+    db: State<'_, DbPool>,
+) -> Result<settings::Settings, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let saved = settings::save(pool, settings).await?;
+        // Turning semantic search on or switching models leaves notes to embed
+        embeddings::schedule();
+        Ok(saved)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Take a backup right now, outside the schedule
+#[tauri::command]
+pub async fn create_backup(
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<backup::BackupInfo, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let config = settings::load(pool).await?.data;
+        let dir = backup::backup_dir(&app_handle, &config)?;
+        let info = backup::create_backup(pool, &dir).await?;
+        backup::rotate_backups(&dir, &config)?;
+        Ok(info)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// List available backups, newest first
+#[tauri::command]
+pub async fn list_backups(
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<Vec<backup::BackupInfo>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let config = settings::load(pool).await?.data;
+        backup::list_backups(&backup::backup_dir(&app_handle, &config)?)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Replace the live database with a backup
+#[tauri::command]
+pub async fn restore_backup(
+    file_name: String,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    backup::restore_backup(&app_handle, &file_name).await?;
+    stats::invalidate();
+    embeddings::schedule();
+    Ok(())
+}
+
+/// Copy a file into the attachment store and attach it to a note
+#[tauri::command]
+pub async fn add_attachment(
+    note_id: String,
+    path: String,
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<attachments::Attachment, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let root = attachments::blob_root(&app_handle)?;
+        attachments::add_attachment(pool, &root, &note_id, std::path::Path::new(&path)).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// List the attachments of a note
+#[tauri::command]
+pub async fn list_attachments(
+    note_id: String,
+    db: State<'_, DbPool>,
+) -> Result<Vec<attachments::Attachment>, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        attachments::list_attachments(pool, &note_id).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Remove an attachment from its note
+#[tauri::command]
+pub async fn remove_attachment(
+    id: String,
+    db: State<'_, DbPool>,
+) -> Result<(), AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        attachments::remove_attachment(pool, &id).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Delete stored files neither the database nor any backup refers to,
+/// returning how many were removed. Only runs when the user asks, since a
+/// backup deleted afterwards can no longer protect its files.
+#[tauri::command]
+pub async fn collect_attachment_garbage(
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<u64, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let root = attachments::blob_root(&app_handle)?;
+        let config = settings::load(pool).await?.data;
+        let dir = backup::backup_dir(&app_handle, &config)?;
+        let backups: Vec<_> = backup::list_backups(&dir)?
+            .into_iter()
+            .map(|b| dir.join(b.file_name))
+            .collect();
+        attachments::collect_garbage(pool, &root, &backups).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Import entries from a Day One, Journey or Markdown export at `path`
+#[tauri::command]
+pub async fn import_notes(
+    format: import::ImportFormat,
+    path: String,
+    db: State<'_, DbPool>,
+) -> Result<import::ImportSummary, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        let summary = import::import_notes(pool, format, std::path::Path::new(&path)).await?;
+        stats::invalidate();
+        embeddings::schedule();
+        Ok(summary)
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Export notes to a Markdown folder, an HTML file or a zip archive
+#[tauri::command]
+pub async fn export_notes(
+    options: export::ExportOptions,
+    db: State<'_, DbPool>,
+) -> Result<export::ExportSummary, AppError> {
+    let pool = db.wait_ready().await?;
+
+    if let Some(pool) = pool.as_ref() {
+        export::export_notes(pool, &options).await
+    } else {
+        Err(AppError::DatabaseLocked)
+    }
+}
+
+/// Whether the database is still opening, ready, locked or failed to open
+#[tauri::command]
+pub fn get_database_status(db: State<'_, DbPool>) -> DatabaseStatus {
+    db.status()
+}
+
+/// Try opening the database again after it failed at startup
+#[tauri::command]
+pub async fn retry_database_initialization(
+    app_handle: AppHandle,
+    db: State<'_, DbPool>,
+) -> Result<DatabaseStatus, AppError> {
+    if !db.begin_retry() {
+        return Err(AppError::InvalidInput("Database is not in a failed state".to_string()));
+    }
+
+    Ok(database::start(&app_handle).await)
+}
+
 /// Check database health status
 #[tauri::command]
 pub async fn check_database_health(
     db: State<'_, DbPool>,
-) -> Result<bool, String> {
-    let pool = db.0.lock().await;
+) -> Result<bool, AppError> {
+    let pool = db.wait_ready().await?;
 
     if let Some(pool) = pool.as_ref() {
         // Run a simple query to verify database is working
         sqlx::query("SELECT 1")
             .execute(pool)
             .await
-            .map_err(|e| format!("Database health check failed: {}", e))?;
+            .map_err(|e| AppError::Storage(format!("Database health check failed: {}", e)))?;
         Ok(true)
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::DatabaseLocked)
     }
 }
 
 /// Check Ollama connection status
 #[tauri::command]
-pub async fn check_ollama_status(url: String) -> Result<OllamaStatus, String> {
+pub async fn check_ollama_status(url: String) -> Result<OllamaStatus, AppError> {
     ollama::check_status(&url).await
 }
 
-/// Send a chat message to Ollama
+/// Send a chat message to Ollama. Passing a `request_id` allows the request
+/// to be stopped with `cancel_chat_request`.
 #[tauri::command]
 pub async fn send_chat_message(
     url: String,
@@ -183,8 +1074,126 @@ pub async fn send_chat_message(
     messages: Vec<ChatMessage>,
     temperature: f32,
     max_tokens: u32,
-) -> Result<ChatMessage, String> {
-    ollama::send_message(&url, &model, messages, temperature, max_tokens).await
+    request_id: Option<String>,
+    requests: State<'_, ollama::ChatRequests>,
+) -> Result<ChatOutcome, AppError> {
+    let request = ollama::send_message(&url, &model, messages, temperature, max_tokens);
+    Ok(requests.run(request_id, request).await?.into())
+}
+
+/// Send a chat message to Ollama and stream the reply over `on_event` as it
+/// is generated. Resolves with the complete message once the stream ends,
+/// or as cancelled if `cancel_chat_request` stopped it first.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_chat_message(
+    url: String,
+    model: String,
+    messages: Vec<ChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    request_id: Option<String>,
+    on_event: Channel<ChatStreamEvent>,
+    requests: State<'_, ollama::ChatRequests>,
+) -> Result<ChatOutcome, AppError> {
+    let on_chunk = |content: &str| {
+        let _ = on_event.send(ChatStreamEvent::Chunk {
+            content: content.to_string(),
+        });
+    };
+    let request = ollama::stream_message(&url, &model, messages, temperature, max_tokens, on_chunk);
+
+    match requests.run(request_id, request).await? {
+        Some((message, stats)) => {
+            let _ = on_event.send(ChatStreamEvent::Done { message: message.clone(), stats });
+            Ok(ChatOutcome::Completed { message })
+        }
+        None => Ok(ChatOutcome::Cancelled),
+    }
+}
+
+/// Answer a question about the journal from the entries most relevant to
+/// it, using the AI settings. The reply streams over `on_event` like
+/// `stream_chat_message`; the result lists the entries the model was given.
+#[tauri::command]
+pub async fn ask_journal(
+    question: String,
+    history: Vec<ChatMessage>,
+    request_id: Option<String>,
+    on_event: Channel<ChatStreamEvent>,
+    requests: State<'_, ollama::ChatRequests>,
+    db: State<'_, DbPool>,
+) -> Result<JournalAnswer, AppError> {
+    // Generating the answer can take a while; don't hold up other commands
+    let pool = db
+        .wait_ready()
+        .await?
+        .clone()
+        .ok_or(AppError::DatabaseLocked)?;
+
+    let config = settings::load(&pool).await?.ai;
+    let today = chrono::Local::now().date_naive();
+    let notes = journal_chat::retrieve(&pool, &config, &question, today).await?;
+
+    let on_chunk = |content: &str| {
+        let _ = on_event.send(ChatStreamEvent::Chunk {
+            content: content.to_string(),
+        });
+    };
+    let request = journal_chat::answer(&config, &question, history, &notes, today, on_chunk);
+
+    match requests.run(request_id, request).await? {
+        Some((message, stats)) => {
+            let sources = journal_chat::sources(&notes, &message.content);
+            let _ = on_event.send(ChatStreamEvent::Done { message: message.clone(), stats });
+            Ok(JournalAnswer {
+                outcome: ChatOutcome::Completed { message },
+                sources,
+            })
+        }
+        None => Ok(JournalAnswer {
+            outcome: ChatOutcome::Cancelled,
+            sources: journal_chat::sources(&notes, ""),
+        }),
+    }
+}
+
+/// Stop a chat request started with the given `request_id`. Returns false
+/// if it had already finished.
+#[tauri::command]
+pub fn cancel_chat_request(request_id: String, requests: State<'_, ollama::ChatRequests>) -> bool {
+    requests.cancel(&request_id)
+}
+
+/// All models installed in Ollama
+#[tauri::command]
+pub async fn list_ollama_models(url: String) -> Result<Vec<ollama::ModelInfo>, AppError> {
+    ollama::list_models(&url).await
+}
+
+/// Details of an installed Ollama model
+#[tauri::command]
+pub async fn show_ollama_model(url: String, model: String) -> Result<ollama::ModelDescription, AppError> {
+    ollama::show_model(&url, &model).await
+}
+
+/// Download a model into Ollama, reporting progress over `on_progress`
+#[tauri::command]
+pub async fn pull_ollama_model(
+    url: String,
+    model: String,
+    on_progress: Channel<ollama::PullProgress>,
+) -> Result<(), AppError> {
+    ollama::pull_model(&url, &model, |progress| {
+        let _ = on_progress.send(progress);
+    })
+    .await
+}
+
+/// Remove a model from Ollama
+#[tauri::command]
+pub async fn delete_ollama_model(url: String, model: String) -> Result<(), AppError> {
+    ollama::delete_model(&url, &model).await
 }
 
 /// Get current weather data
@@ -192,13 +1201,13 @@ pub async fn send_chat_message(
 pub async fn get_weather(
     api_key: String,
     location: String,
-) -> Result<weather::WeatherData, String> {
+) -> Result<weather::WeatherData, AppError> {
     weather::fetch_weather(&api_key, &location).await
 }
 
 /// Auto-detect location from IP
 #[tauri::command]
-pub async fn detect_location() -> Result<String, String> {
+pub async fn detect_location() -> Result<String, AppError> {
     weather::detect_location().await
 }
 
@@ -207,7 +1216,7 @@ pub async fn detect_location() -> Result<String, String> {
 pub async fn get_journal_context(
     api_key: String,
     location: String,
-) -> Result<weather::JournalContext, String> {
+) -> Result<weather::JournalContext, AppError> {
     weather::get_journal_context(&api_key, &location).await
 }
 
@@ -218,7 +1227,7 @@ pub async fn get_journal_context(
 pub async fn nlc_validate_entry(
     api_url: String,
     entry: natlangchain::NatLangChainEntry,
-) -> Result<natlangchain::ValidationResult, String> {
+) -> Result<natlangchain::ValidationResult, AppError> {
     natlangchain::validate_entry(&api_url, &entry).await
 }
 
@@ -227,7 +1236,7 @@ pub async fn nlc_validate_entry(
 pub async fn nlc_publish_entry(
     api_url: String,
     entry: natlangchain::NatLangChainEntry,
-) -> Result<natlangchain::PublishResult, String> {
+) -> Result<natlangchain::PublishResult, AppError> {
     natlangchain::publish_entry(&api_url, &entry).await
 }
 
@@ -236,12 +1245,12 @@ pub async fn nlc_publish_entry(
 pub async fn nlc_get_stats(
     api_url: String,
     author_id: String,
-) -> Result<natlangchain::ChainStats, String> {
+) -> Result<natlangchain::ChainStats, AppError> {
     natlangchain::get_author_stats(&api_url, &author_id).await
 }
 
 /// Check NatLangChain API connection
 #[tauri::command]
-pub async fn nlc_check_connection(api_url: String) -> Result<bool, String> {
+pub async fn nlc_check_connection(api_url: String) -> Result<bool, AppError> {
     natlangchain::check_connection(&api_url).await
 }
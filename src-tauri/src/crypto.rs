@@ -0,0 +1,384 @@
+use crate::commands::Note;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use tokio::sync::Mutex;
+
+/// Prefix marking an encrypted column value, so plaintext and ciphertext can
+/// be told apart (and the format can evolve) without a schema change.
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+/// Header marking an encrypted binary blob; followed by nonce || ciphertext
+const ENCRYPTED_BLOB_MAGIC: &[u8] = b"HLPRENC1";
+const SALT_KEY: &str = "encryption_salt";
+const WRAPPED_KEY_KEY: &str = "encryption_wrapped_key";
+const MIN_PASSPHRASE_LEN: usize = 8;
+const NONCE_LEN: usize = 24;
+
+/// The unlocked data key. It is process-wide, like the database it protects,
+/// so every module that reads or writes note text can reach it without each
+/// command having to thread an extra piece of state through.
+static ACTIVE_CIPHER: RwLock<Option<XChaCha20Poly1305>> = RwLock::new(None);
+
+/// Holds the open pool while an encrypted database waits for its passphrase.
+/// `DbPool` stays empty until `unlock` moves the pool across.
+pub struct LockedPool(pub Mutex<Option<SqlitePool>>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+/// Encrypt under a fresh random nonce, returning nonce || ciphertext
+fn seal(cipher: &XChaCha20Poly1305, plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| AppError::Internal("Encryption failed".to_string()))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(payload)
+}
+
+fn unseal(cipher: &XChaCha20Poly1305, payload: &[u8]) -> Result<Vec<u8>, AppError> {
+    if payload.len() < NONCE_LEN {
+        return Err(AppError::Storage(
+            "Corrupt encrypted value: too short".to_string(),
+        ));
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            AppError::Storage("Decryption failed: wrong key or corrupted data".to_string())
+        })
+}
+
+fn encrypt_with(cipher: &XChaCha20Poly1305, plaintext: &[u8]) -> Result<String, AppError> {
+    Ok(format!(
+        "{}{}",
+        ENCRYPTED_PREFIX,
+        BASE64.encode(seal(cipher, plaintext)?)
+    ))
+}
+
+fn decrypt_with(cipher: &XChaCha20Poly1305, value: &str) -> Result<Vec<u8>, AppError> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| AppError::Storage("Value is not encrypted".to_string()))?;
+    let payload = BASE64
+        .decode(encoded)
+        .map_err(|e| AppError::Storage(format!("Corrupt encrypted value: {}", e)))?;
+
+    unseal(cipher, &payload)
+}
+
+/// Derive the key-encryption key from the passphrase
+fn derive_kek(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, AppError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::Internal(format!("Key derivation failed: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn validate_passphrase(passphrase: &str) -> Result<(), AppError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Wrap a data key under a fresh salt and passphrase, returning (salt, wrapped key)
+fn wrap_data_key(passphrase: &str, data_key: &[u8]) -> Result<(String, String), AppError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let kek = derive_kek(passphrase, &salt)?;
+    Ok((BASE64.encode(salt), encrypt_with(&kek, data_key)?))
+}
+
+async fn read_metadata(pool: &SqlitePool, key: &str) -> Result<Option<String>, AppError> {
+    sqlx::query_scalar("SELECT value FROM metadata WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(AppError::from)
+}
+
+fn not_enabled() -> AppError {
+    AppError::InvalidInput("Encryption is not enabled".to_string())
+}
+
+/// Unwrap the data key with the passphrase; fails on a wrong passphrase
+async fn unwrap_data_key(pool: &SqlitePool, passphrase: &str) -> Result<Vec<u8>, AppError> {
+    let salt = read_metadata(pool, SALT_KEY)
+        .await?
+        .ok_or_else(not_enabled)?;
+    let wrapped = read_metadata(pool, WRAPPED_KEY_KEY)
+        .await?
+        .ok_or_else(not_enabled)?;
+
+    let salt = BASE64
+        .decode(salt)
+        .map_err(|e| AppError::Storage(format!("Corrupt encryption salt: {}", e)))?;
+    let kek = derive_kek(passphrase, &salt)?;
+
+    decrypt_with(&kek, &wrapped).map_err(|_| AppError::WrongPassphrase)
+}
+
+async fn store_wrapped_key(
+    conn: &mut sqlx::SqliteConnection,
+    salt: &str,
+    wrapped: &str,
+) -> Result<(), AppError> {
+    for (key, value) in [(SALT_KEY, salt), (WRAPPED_KEY_KEY, wrapped)] {
+        sqlx::query(
+            r#"
+            INSERT INTO metadata (key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Whether the database has encryption configured
+pub async fn is_configured(pool: &SqlitePool) -> Result<bool, AppError> {
+    Ok(read_metadata(pool, WRAPPED_KEY_KEY).await?.is_some())
+}
+
+/// Whether note text is currently being encrypted on write
+pub fn is_active() -> bool {
+    ACTIVE_CIPHER.read().map(|c| c.is_some()).unwrap_or(false)
+}
+
+fn activate(data_key: &[u8]) {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(data_key));
+    if let Ok(mut active) = ACTIVE_CIPHER.write() {
+        *active = Some(cipher);
+    }
+}
+
+/// Forget the data key, e.g. before switching to a different database file
+pub fn lock() {
+    if let Ok(mut active) = ACTIVE_CIPHER.write() {
+        *active = None;
+    }
+}
+
+/// Encrypt a piece of note text if encryption is active, otherwise pass it through
+pub fn encrypt_text(value: &str) -> Result<String, AppError> {
+    let active = ACTIVE_CIPHER
+        .read()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    match active.as_ref() {
+        Some(cipher) => encrypt_with(cipher, value.as_bytes()),
+        None => Ok(value.to_string()),
+    }
+}
+
+pub fn encrypt_optional(value: &Option<String>) -> Result<Option<String>, AppError> {
+    value.as_deref().map(encrypt_text).transpose()
+}
+
+/// Decrypt a stored value. Without an active key nothing is stored encrypted,
+/// so values are returned as-is even if they happen to start with the prefix.
+pub fn decrypt_text(value: &str) -> Result<String, AppError> {
+    let active = ACTIVE_CIPHER
+        .read()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let cipher = match active.as_ref() {
+        Some(cipher) if value.starts_with(ENCRYPTED_PREFIX) => cipher,
+        _ => return Ok(value.to_string()),
+    };
+    let plaintext = decrypt_with(cipher, value)?;
+    String::from_utf8(plaintext)
+        .map_err(|e| AppError::Storage(format!("Decrypted text is not UTF-8: {}", e)))
+}
+
+pub fn decrypt_optional(value: &Option<String>) -> Result<Option<String>, AppError> {
+    value.as_deref().map(decrypt_text).transpose()
+}
+
+/// Whether a binary blob was written by `encrypt_blob`
+pub fn is_encrypted_blob(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_BLOB_MAGIC)
+}
+
+/// Encrypt binary data (e.g. an attachment) if encryption is active
+pub fn encrypt_blob(data: Vec<u8>) -> Result<Vec<u8>, AppError> {
+    let active = ACTIVE_CIPHER
+        .read()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    match active.as_ref() {
+        Some(cipher) => {
+            let mut blob = ENCRYPTED_BLOB_MAGIC.to_vec();
+            blob.extend_from_slice(&seal(cipher, &data)?);
+            Ok(blob)
+        }
+        None => Ok(data),
+    }
+}
+
+/// Decrypt binary data; like `decrypt_text`, blobs are returned as-is
+/// unless a key is active and they carry the encryption header
+pub fn decrypt_blob(data: Vec<u8>) -> Result<Vec<u8>, AppError> {
+    let active = ACTIVE_CIPHER
+        .read()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let cipher = match active.as_ref() {
+        Some(cipher) if is_encrypted_blob(&data) => cipher,
+        _ => return Ok(data),
+    };
+    unseal(cipher, &data[ENCRYPTED_BLOB_MAGIC.len()..])
+}
+
+/// Decrypt the title and content of notes loaded straight from the database
+pub fn decrypt_notes(notes: &mut [Note]) -> Result<(), AppError> {
+    for note in notes.iter_mut() {
+        note.title = decrypt_optional(&note.title)?;
+        note.content = decrypt_text(&note.content)?;
+    }
+    Ok(())
+}
+
+/// Verify the passphrase and make the data key available for this session
+pub async fn unlock(pool: &SqlitePool, passphrase: &str) -> Result<(), AppError> {
+    let data_key = unwrap_data_key(pool, passphrase).await?;
+    activate(&data_key);
+    Ok(())
+}
+
+/// Encrypt every note, revision and template in place and start encrypting new writes
+pub async fn enable(pool: &SqlitePool, passphrase: &str) -> Result<(), AppError> {
+    validate_passphrase(passphrase)?;
+    if is_configured(pool).await? {
+        return Err(AppError::InvalidInput(
+            "Encryption is already enabled".to_string(),
+        ));
+    }
+
+    let data_key = XChaCha20Poly1305::generate_key(&mut OsRng);
+    let cipher = XChaCha20Poly1305::new(&data_key);
+    let (salt, wrapped) = wrap_data_key(passphrase, &data_key)?;
+
+    let mut tx = pool.begin().await?;
+
+    for table in ["notes", "note_revisions"] {
+        let rows: Vec<(String, Option<String>, String)> =
+            sqlx::query_as(&format!("SELECT id, title, content FROM {}", table))
+                .fetch_all(&mut *tx)
+                .await?;
+
+        for (id, title, content) in rows {
+            let title = title
+                .map(|t| encrypt_with(&cipher, t.as_bytes()))
+                .transpose()?;
+            let content = encrypt_with(&cipher, content.as_bytes())?;
+
+            sqlx::query(&format!(
+                "UPDATE {} SET title = ?, content = ? WHERE id = ?",
+                table
+            ))
+            .bind(title)
+            .bind(content)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    // Link targets are note titles, so they must not stay readable either
+    let links: Vec<(String, i64, String)> =
+        sqlx::query_as("SELECT source_id, position, target FROM note_links")
+            .fetch_all(&mut *tx)
+            .await?;
+
+    for (source_id, position, target) in links {
+        sqlx::query("UPDATE note_links SET target = ? WHERE source_id = ? AND position = ?")
+            .bind(encrypt_with(&cipher, target.as_bytes())?)
+            .bind(&source_id)
+            .bind(position)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let templates: Vec<(String, String, String)> =
+        sqlx::query_as("SELECT id, name, content FROM templates")
+            .fetch_all(&mut *tx)
+            .await?;
+
+    for (id, name, content) in templates {
+        sqlx::query("UPDATE templates SET name = ?, content = ? WHERE id = ?")
+            .bind(encrypt_with(&cipher, name.as_bytes())?)
+            .bind(encrypt_with(&cipher, content.as_bytes())?)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Embeddings are recomputed from the encrypted notes rather than
+    // rewritten, and stored encrypted from then on
+    sqlx::query("DELETE FROM note_embeddings")
+        .execute(&mut *tx)
+        .await?;
+
+    // Re-index from the now encrypted rows so the search index no longer holds
+    // a plaintext copy of every note (and stays consistent with its triggers)
+    sqlx::query("INSERT INTO notes_fts(notes_fts) VALUES ('rebuild')")
+        .execute(&mut *tx)
+        .await?;
+
+    store_wrapped_key(&mut tx, &salt, &wrapped).await?;
+    tx.commit().await?;
+
+    activate(&data_key);
+
+    // Rewrite the file so freed pages no longer hold the old plaintext. VACUUM
+    // may renumber the implicit rowids of `notes` that the search index refers
+    // to, so index it again afterwards.
+    sqlx::query("VACUUM").execute(pool).await.map_err(|e| {
+        AppError::Storage(format!(
+            "Encryption enabled, but compacting the database failed: {}",
+            e
+        ))
+    })?;
+    sqlx::query("INSERT INTO notes_fts(notes_fts) VALUES ('rebuild')")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Re-wrap the data key under a new passphrase; note data is not rewritten
+pub async fn change_passphrase(
+    pool: &SqlitePool,
+    current: &str,
+    new_passphrase: &str,
+) -> Result<(), AppError> {
+    validate_passphrase(new_passphrase)?;
+    let data_key = unwrap_data_key(pool, current).await?;
+    let (salt, wrapped) = wrap_data_key(new_passphrase, &data_key)?;
+
+    let mut tx = pool.begin().await?;
+    store_wrapped_key(&mut tx, &salt, &wrapped).await?;
+    tx.commit().await?;
+    Ok(())
+}
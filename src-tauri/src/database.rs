@@ -1,25 +1,340 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::{AppHandle, Manager};
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{watch, Mutex, MutexGuard};
 
-// For now, we'll use a simple approach without sqlx compile-time checks
-// In production, you'd want to set up proper SQLx with migrations
-
-pub struct DbPool(pub Arc<Mutex<Option<SqlitePool>>>);
+/// The open database, registered empty at startup and filled in once
+/// `initialize` has opened it. The second field tracks how far that got so
+/// commands arriving early can wait instead of failing.
+pub struct DbPool(
+    pub Arc<Mutex<Option<SqlitePool>>>,
+    watch::Sender<DatabaseStatus>,
+);
 
 pub type SqlitePool = sqlx::SqlitePool;
 
-/// Initialize the database connection and create tables
-pub async fn initialize(app_handle: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+/// How long a command waits for the database to open before giving up
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum DatabaseStatus {
+    Initializing,
+    Ready,
+    /// Encrypted and waiting for the passphrase
+    Locked,
+    Failed {
+        message: String,
+    },
+}
+
+impl Default for DbPool {
+    fn default() -> Self {
+        DbPool(
+            Arc::new(Mutex::new(None)),
+            watch::Sender::new(DatabaseStatus::Initializing),
+        )
+    }
+}
+
+impl DbPool {
+    pub fn status(&self) -> DatabaseStatus {
+        self.1.borrow().clone()
+    }
+
+    pub fn set_status(&self, status: DatabaseStatus) {
+        self.1.send_replace(status);
+    }
+
+    /// Move a failed startup back to `Initializing`, returning false if it
+    /// had not failed. Checking and setting in one step keeps two quick
+    /// retries from both opening the database.
+    pub fn begin_retry(&self) -> bool {
+        self.1.send_if_modified(|status| {
+            if matches!(status, DatabaseStatus::Failed { .. }) {
+                *status = DatabaseStatus::Initializing;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Wait until startup has either opened the database or failed to
+    pub async fn wait_until_open(&self) -> Result<(), AppError> {
+        let mut status = self.1.subscribe();
+        let opened = tokio::time::timeout(
+            READY_TIMEOUT,
+            status.wait_for(|s| *s != DatabaseStatus::Initializing),
+        )
+        .await
+        .map_err(|_| {
+            AppError::DatabaseUnavailable("Timed out waiting for the database to open".to_string())
+        })?
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        match &*opened {
+            DatabaseStatus::Failed { message } => Err(AppError::DatabaseUnavailable(format!(
+                "Database could not be opened: {}",
+                message
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock the pool once the database is open. The pool is still `None`
+    /// afterwards while an encrypted database is locked.
+    pub async fn wait_ready(&self) -> Result<MutexGuard<'_, Option<SqlitePool>>, AppError> {
+        self.wait_until_open().await?;
+        Ok(self.0.lock().await)
+    }
+}
+
+/// Metadata key holding the currently applied schema version
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+/// Settings file the frontend kept with `tauri-plugin-store`, next to `helper.db`
+const SETTINGS_STORE_FILE: &str = "settings.json";
+
+/// A single, ordered schema change. Once released, a migration must never be
+/// edited; add a new one with the next version number instead.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS notes (
+            id          TEXT PRIMARY KEY,
+            date        TEXT NOT NULL,
+            title       TEXT,
+            content     TEXT NOT NULL,
+            created_at  TEXT NOT NULL,
+            updated_at  TEXT NOT NULL,
+            deleted_at  TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_notes_date ON notes(date);
+        CREATE INDEX IF NOT EXISTS idx_notes_deleted ON notes(deleted_at);
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key         TEXT PRIMARY KEY,
+            value       TEXT NOT NULL,
+            updated_at  TEXT NOT NULL
+        );
+    "#,
+    },
+    Migration {
+        version: 2,
+        description: "full-text search index over notes",
+        sql: r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
+            title,
+            content,
+            content='notes',
+            content_rowid='rowid',
+            tokenize='unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
+            INSERT INTO notes_fts(rowid, title, content)
+            VALUES (new.rowid, new.title, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
+            INSERT INTO notes_fts(notes_fts, rowid, title, content)
+            VALUES ('delete', old.rowid, old.title, old.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE OF title, content ON notes BEGIN
+            INSERT INTO notes_fts(notes_fts, rowid, title, content)
+            VALUES ('delete', old.rowid, old.title, old.content);
+            INSERT INTO notes_fts(rowid, title, content)
+            VALUES (new.rowid, new.title, new.content);
+        END;
+
+        INSERT INTO notes_fts(notes_fts) VALUES ('rebuild');
+    "#,
+    },
+    Migration {
+        version: 3,
+        description: "note revision history",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS note_revisions (
+            id          TEXT PRIMARY KEY,
+            note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            title       TEXT,
+            content     TEXT NOT NULL,
+            created_at  TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_revisions_note
+            ON note_revisions(note_id, created_at);
+    "#,
+    },
+    Migration {
+        version: 4,
+        description: "tags and note_tags",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS tags (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            name        TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at  TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS note_tags (
+            note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            tag_id      INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (note_id, tag_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_tags_tag ON note_tags(tag_id);
+    "#,
+    },
+    Migration {
+        version: 5,
+        description: "keyset pagination index",
+        sql: r#"
+        CREATE INDEX IF NOT EXISTS idx_notes_date_created
+            ON notes(date, created_at, id);
+    "#,
+    },
+    Migration {
+        version: 6,
+        description: "journal context snapshots",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS note_context (
+            note_id           TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+            day_of_week       TEXT NOT NULL,
+            time_of_day       TEXT NOT NULL,
+            moon_phase        TEXT,
+            weather_condition TEXT,
+            temp_celsius      REAL,
+            location          TEXT,
+            weather_json      TEXT,
+            captured_at       TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_context_condition
+            ON note_context(weather_condition);
+    "#,
+    },
+    Migration {
+        version: 7,
+        description: "import provenance for de-duplication",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS note_imports (
+            source      TEXT NOT NULL,
+            external_id TEXT NOT NULL,
+            note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            imported_at TEXT NOT NULL,
+            PRIMARY KEY (source, external_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_imports_note ON note_imports(note_id);
+    "#,
+    },
+    Migration {
+        version: 8,
+        description: "note attachments",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS attachments (
+            id          TEXT PRIMARY KEY,
+            note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            hash        TEXT NOT NULL,
+            file_name   TEXT NOT NULL,
+            mime_type   TEXT NOT NULL,
+            size_bytes  INTEGER NOT NULL,
+            created_at  TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_attachments_note ON attachments(note_id);
+        CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(hash);
+    "#,
+    },
+    Migration {
+        version: 9,
+        description: "wiki links between notes",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS note_links (
+            source_id       TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            position        INTEGER NOT NULL,
+            target          TEXT NOT NULL,
+            target_note_id  TEXT REFERENCES notes(id) ON DELETE SET NULL,
+            target_date     TEXT,
+            PRIMARY KEY (source_id, position)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_links_target_note ON note_links(target_note_id);
+        CREATE INDEX IF NOT EXISTS idx_note_links_target_date ON note_links(target_date);
+    "#,
+    },
+    Migration {
+        version: 10,
+        description: "note templates",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS templates (
+            id          TEXT PRIMARY KEY,
+            name        TEXT NOT NULL,
+            content     TEXT NOT NULL,
+            created_at  TEXT NOT NULL,
+            updated_at  TEXT NOT NULL
+        );
+
+        -- At most one default template per weekday, 0 = Monday
+        CREATE TABLE IF NOT EXISTS template_defaults (
+            weekday      INTEGER PRIMARY KEY CHECK (weekday BETWEEN 0 AND 6),
+            template_id  TEXT NOT NULL REFERENCES templates(id) ON DELETE CASCADE
+        );
+    "#,
+    },
+    Migration {
+        version: 11,
+        description: "note embeddings",
+        sql: r#"
+        -- One vector per note, from the embedding model named in `model`.
+        -- `note_updated_at` is the note version that was embedded, so edited
+        -- notes and a changed model both show up as stale.
+        CREATE TABLE IF NOT EXISTS note_embeddings (
+            note_id          TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+            model            TEXT NOT NULL,
+            note_updated_at  TEXT NOT NULL,
+            vector           BLOB NOT NULL,
+            updated_at       TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_note_embeddings_model ON note_embeddings(model);
+    "#,
+    },
+];
+
+/// Latest schema version known to this build
+fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Location of `helper.db` inside the app data directory, created if missing
+pub fn database_path(app_handle: &AppHandle) -> Result<PathBuf, Box<dyn std::error::Error>> {
     let app_dir = app_handle
         .path()
         .app_data_dir()
-        .expect("Failed to get app data directory");
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
 
     // Create directory if it doesn't exist
     std::fs::create_dir_all(&app_dir)?;
 
-    let db_path = app_dir.join("helper.db");
+    Ok(app_dir.join("helper.db"))
+}
+
+/// Open a connection pool to the database file and bring its schema up to date
+pub async fn open(db_path: &Path) -> Result<SqlitePool, Box<dyn std::error::Error>> {
     let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
 
     // Connect to database
@@ -28,66 +343,81 @@ pub async fn initialize(app_handle: &AppHandle) -> Result<(), Box<dyn std::error
         .connect(&db_url)
         .await?;
 
-    // Run migrations / create tables
-    create_tables(&pool).await?;
+    migrate(&pool, db_path).await?;
+
+    Ok(pool)
+}
+
+/// Initialize the database connection and bring the schema up to date
+pub async fn initialize(app_handle: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = database_path(app_handle)?;
+    let pool = open(&db_path).await?;
+
+    if let Err(e) =
+        crate::settings::migrate_legacy(&pool, &db_path.with_file_name(SETTINGS_STORE_FILE)).await
+    {
+        eprintln!("Failed to migrate settings: {}", e);
+    }
+
+    // Trash cleanup is housekeeping; a failure here must not block startup
+    match crate::trash::purge_expired(&pool).await {
+        Ok(0) => {}
+        Ok(purged) => println!("Purged {} expired note(s) from the trash", purged),
+        Err(e) => eprintln!("Failed to purge expired trash: {}", e),
+    }
 
-    // Store pool in app state
-    app_handle.manage(DbPool(Arc::new(Mutex::new(Some(pool)))));
+    // An encrypted database stays out of app state until the user unlocks it
+    let db = app_handle.state::<DbPool>();
+    if crate::crypto::is_configured(&pool).await? {
+        *app_handle
+            .state::<crate::crypto::LockedPool>()
+            .0
+            .lock()
+            .await = Some(pool);
+        db.set_status(DatabaseStatus::Locked);
+        let _ = app_handle.emit("database-locked", ());
+    } else {
+        if let Err(e) = crate::links::rebuild_if_needed(&pool).await {
+            eprintln!("Failed to index note links: {}", e);
+        }
+        *db.0.lock().await = Some(pool);
+        db.set_status(DatabaseStatus::Ready);
+        let _ = app_handle.emit("database-ready", ());
+    }
 
     Ok(())
 }
 
-/// Create database tables if they don't exist
-async fn create_tables(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    // Notes table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS notes (
-            id          TEXT PRIMARY KEY,
-            date        TEXT NOT NULL,
-            title       TEXT,
-            content     TEXT NOT NULL,
-            created_at  TEXT NOT NULL,
-            updated_at  TEXT NOT NULL,
-            deleted_at  TEXT
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Open the database and report the outcome: `database-ready`,
+/// `database-locked` or `database-failed` (with the error message). Also
+/// used to retry after a failure.
+pub async fn start(app_handle: &AppHandle) -> DatabaseStatus {
+    let db = app_handle.state::<DbPool>();
+    db.set_status(DatabaseStatus::Initializing);
 
-    // Index on date for quick lookups
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_notes_date ON notes(date)
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    let message = match initialize(app_handle).await {
+        Ok(()) => {
+            crate::backup::start_scheduler(app_handle.clone());
+            crate::embeddings::start_worker(app_handle.clone());
+            return db.status();
+        }
+        Err(e) => e.to_string(),
+    };
 
-    // Index on deleted_at for filtering
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_notes_deleted ON notes(deleted_at)
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    eprintln!("Failed to initialize database: {}", message);
+    let status = DatabaseStatus::Failed {
+        message: message.clone(),
+    };
+    db.set_status(status.clone());
+    let _ = app_handle.emit("database-failed", message);
 
-    // Settings table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS settings (
-            key         TEXT PRIMARY KEY,
-            value       TEXT NOT NULL,
-            updated_at  TEXT NOT NULL
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    status
+}
 
-    // Metadata table
+/// Apply all pending migrations in order, each inside its own transaction
+async fn migrate(pool: &SqlitePool, db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // The metadata table stores the schema version, so it has to exist
+    // before anything else can be decided.
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS metadata (
@@ -99,5 +429,112 @@ async fn create_tables(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    let current = schema_version(pool).await?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this version of HeLpER supports ({}). \
+             Please update the application.",
+            current, latest
+        )
+        .into());
+    }
+
+    if current == latest {
+        return Ok(());
+    }
+
+    if has_existing_data(pool).await? {
+        let backup_path = backup_before_migration(pool, db_path, current).await?;
+        println!(
+            "Backed up database to {} before migrating to schema version {}",
+            backup_path.display(),
+            latest
+        );
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+
+        // Executing the plain string runs every statement in the script
+        // (`raw_sql` would too, but its future is not `Send`)
+        sqlx::Executor::execute(&mut *tx, migration.sql)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.description, e
+                )
+            })?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO metadata (key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value
+            "#,
+        )
+        .bind(SCHEMA_VERSION_KEY)
+        .bind(migration.version.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+
     Ok(())
 }
+
+/// Read the applied schema version, treating a missing entry as version 0
+async fn schema_version(pool: &SqlitePool) -> Result<i64, Box<dyn std::error::Error>> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM metadata WHERE key = ?")
+        .bind(SCHEMA_VERSION_KEY)
+        .fetch_optional(pool)
+        .await?;
+
+    match value {
+        Some(v) => v
+            .parse::<i64>()
+            .map_err(|e| format!("Invalid schema version '{}': {}", v, e).into()),
+        None => Ok(0),
+    }
+}
+
+/// Whether the database already holds user tables (i.e. is not a fresh file)
+async fn has_existing_data(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'notes'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// Write a consistent copy of the database next to the original before migrating
+async fn backup_before_migration(
+    pool: &SqlitePool,
+    db_path: &Path,
+    from_version: i64,
+) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    let file_name = format!(
+        "{}.pre-migration-v{}-{}.bak",
+        db_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("helper.db"),
+        from_version,
+        timestamp
+    );
+    let backup_path = db_path.with_file_name(file_name);
+
+    // VACUUM INTO produces a transactionally consistent copy even while the
+    // pool holds open connections, unlike a plain file copy.
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await?;
+
+    Ok(backup_path)
+}
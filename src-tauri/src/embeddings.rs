@@ -0,0 +1,281 @@
+use crate::commands::Note;
+use crate::crypto;
+use crate::database::{DbPool, SqlitePool};
+use crate::error::AppError;
+use crate::notes;
+use crate::ollama;
+use crate::settings;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+/// Notes embedded per request, so a large backlog never blocks Ollama for long
+const BATCH_SIZE: i64 = 16;
+/// Saves come in bursts while typing; wait for them to settle before embedding
+const DEBOUNCE_SECS: u64 = 2;
+/// Check for stale notes now and then even without a wake-up, e.g. after an
+/// import or restore, or to retry once Ollama is back
+const IDLE_SECS: u64 = 10 * 60;
+/// Only the start of very long notes is embedded
+const MAX_INPUT_CHARS: usize = 8000;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 50;
+
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+static WAKE: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticHit {
+    pub note: Note,
+    /// Cosine similarity to the query; higher is a better match
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticResults {
+    pub hits: Vec<SemanticHit>,
+    /// Notes not yet embedded with the current model, which cannot match
+    pub pending: i64,
+}
+
+/// Let the background worker know notes changed; call after anything that
+/// creates or edits notes
+pub fn schedule() {
+    WAKE.notify_one();
+}
+
+fn to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+/// The text a note is embedded from
+fn note_text(note: &Note) -> String {
+    let text = match note.title.as_deref() {
+        Some(title) if !title.trim().is_empty() => format!("{}\n\n{}", title, note.content),
+        _ => note.content.clone(),
+    };
+    text.chars().take(MAX_INPUT_CHARS).collect()
+}
+
+/// Number of live notes without an up-to-date embedding from `model`
+async fn count_pending(pool: &SqlitePool, model: &str) -> Result<i64, AppError> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM notes n
+        LEFT JOIN note_embeddings e ON e.note_id = n.id
+        WHERE n.deleted_at IS NULL
+          AND (e.note_id IS NULL OR e.model != ? OR e.note_updated_at != n.updated_at)
+        "#,
+    )
+    .bind(model)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Embed the next batch of new, edited or differently embedded notes,
+/// returning how many were embedded
+pub async fn embed_pending(pool: &SqlitePool) -> Result<usize, AppError> {
+    let config = settings::load(pool).await?.ai;
+    if !config.semantic_search {
+        return Ok(0);
+    }
+
+    let mut stale = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT n.id, n.date, n.title, n.content, n.created_at, n.updated_at, n.deleted_at
+        FROM notes n
+        LEFT JOIN note_embeddings e ON e.note_id = n.id
+        WHERE n.deleted_at IS NULL
+          AND (e.note_id IS NULL OR e.model != ? OR e.note_updated_at != n.updated_at)
+        ORDER BY n.updated_at DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(&config.embedding_model)
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    if stale.is_empty() {
+        return Ok(0);
+    }
+
+    crypto::decrypt_notes(&mut stale)?;
+    let texts: Vec<String> = stale.iter().map(note_text).collect();
+
+    // Empty notes get an empty vector so they count as done without asking
+    // the model to embed nothing
+    let to_embed: Vec<String> = texts
+        .iter()
+        .filter(|text| !text.trim().is_empty())
+        .cloned()
+        .collect();
+    let mut vectors = if to_embed.is_empty() {
+        Vec::new()
+    } else {
+        ollama::embed(&config.ollama_url, &config.embedding_model, &to_embed).await?
+    }
+    .into_iter();
+
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let mut tx = pool.begin().await?;
+    for (note, text) in stale.iter().zip(&texts) {
+        let vector = if text.trim().is_empty() {
+            Vec::new()
+        } else {
+            vectors.next().unwrap_or_default()
+        };
+
+        // Storing the version that was embedded means a note edited in the
+        // meantime simply stays stale and is picked up again
+        sqlx::query(
+            r#"
+            INSERT INTO note_embeddings (note_id, model, note_updated_at, vector, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(note_id) DO UPDATE SET
+                model = excluded.model,
+                note_updated_at = excluded.note_updated_at,
+                vector = excluded.vector,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&note.id)
+        .bind(&config.embedding_model)
+        .bind(&note.updated_at)
+        .bind(crypto::encrypt_blob(to_bytes(&vector))?)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(stale.len())
+}
+
+/// Start the background task that keeps note embeddings up to date. Only the
+/// first call does anything, so retrying startup cannot start a second one.
+pub fn start_worker(app_handle: AppHandle) {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Work through a backlog batch by batch, then wait for changes
+            let pool = app_handle.state::<DbPool>().0.lock().await.clone();
+            let embedded = match pool {
+                Some(pool) => embed_pending(&pool).await.unwrap_or_else(|e| {
+                    eprintln!("Embedding notes failed: {}", e);
+                    0
+                }),
+                None => 0,
+            };
+            if embedded as i64 == BATCH_SIZE {
+                continue;
+            }
+
+            let _ = tokio::time::timeout(Duration::from_secs(IDLE_SECS), WAKE.notified()).await;
+            tokio::time::sleep(Duration::from_secs(DEBOUNCE_SECS)).await;
+        }
+    });
+}
+
+/// Notes most similar in meaning to `query`, best first
+pub async fn semantic_search(
+    pool: &SqlitePool,
+    query: &str,
+    limit: Option<u32>,
+) -> Result<SemanticResults, AppError> {
+    let config = settings::load(pool).await?.ai;
+    if !config.semantic_search {
+        return Err(AppError::InvalidInput(
+            "Semantic search is turned off in settings".to_string(),
+        ));
+    }
+
+    let pending = count_pending(pool, &config.embedding_model).await?;
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(SemanticResults {
+            hits: Vec::new(),
+            pending,
+        });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    let query_vector = ollama::embed(
+        &config.ollama_url,
+        &config.embedding_model,
+        &[query.to_string()],
+    )
+    .await?
+    .pop()
+    .unwrap_or_default();
+
+    let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+        r#"
+        SELECT e.note_id, e.vector
+        FROM note_embeddings e
+        JOIN notes n ON n.id = e.note_id
+        WHERE n.deleted_at IS NULL AND e.model = ?
+        "#,
+    )
+    .bind(&config.embedding_model)
+    .fetch_all(pool)
+    .await?;
+
+    let mut scored = Vec::with_capacity(rows.len());
+    for (note_id, vector) in rows {
+        let vector = from_bytes(&crypto::decrypt_blob(vector)?);
+        if !vector.is_empty() {
+            scored.push((note_id, cosine_similarity(&query_vector, &vector)));
+        }
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(limit);
+
+    let mut hits = Vec::with_capacity(scored.len());
+    for (note_id, score) in scored {
+        if let Some(note) = notes::get_note(pool, &note_id).await? {
+            hits.push(SemanticHit { note, score });
+        }
+    }
+
+    Ok(SemanticResults { hits, pending })
+}
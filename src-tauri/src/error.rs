@@ -0,0 +1,244 @@
+use crate::commands::Note;
+use reqwest::StatusCode;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// Broad kind of failure, for the UI to decide how to present an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// A remote service (Ollama, weather, NatLangChain) could not be reached
+    /// or answered with an error
+    Network,
+    /// A passphrase or credential is missing or wrong
+    Auth,
+    /// The request itself was invalid; retrying it unchanged will not help
+    Validation,
+    /// Reading or writing the database or files failed
+    Storage,
+    NotFound,
+    /// The data changed underneath the request
+    Conflict,
+    Internal,
+}
+
+/// Error returned by every command. It reaches the frontend as
+/// `{ code, category, retryable, message, details? }`, where `code` is a
+/// stable identifier to match on and `message` is fit to show the user.
+#[derive(Debug)]
+pub enum AppError {
+    /// The database has not finished opening or failed to
+    DatabaseUnavailable(String),
+    /// The database is encrypted and waiting for the passphrase
+    DatabaseLocked,
+    /// SQLite is busy with another writer
+    DatabaseBusy,
+    WrongPassphrase,
+    Storage(String),
+    InvalidInput(String),
+    NotFound(String),
+    /// The note was changed since the caller last read it; carries the
+    /// saved version so the caller can merge
+    Conflict(Box<Note>),
+    /// Nothing answered at the configured Ollama URL
+    OllamaOffline(String),
+    ModelNotFound(String),
+    Network(String),
+    Timeout(String),
+    /// A remote service rejected the credentials it was given
+    Unauthorized(String),
+    /// A remote service answered with an error
+    Service {
+        message: String,
+        retryable: bool,
+    },
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::DatabaseUnavailable(_) => "database_unavailable",
+            AppError::DatabaseLocked => "database_locked",
+            AppError::DatabaseBusy => "database_busy",
+            AppError::WrongPassphrase => "wrong_passphrase",
+            AppError::Storage(_) => "storage",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::OllamaOffline(_) => "ollama_offline",
+            AppError::ModelNotFound(_) => "model_not_found",
+            AppError::Network(_) => "network",
+            AppError::Timeout(_) => "timeout",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Service { .. } => "service",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AppError::DatabaseUnavailable(_) | AppError::DatabaseBusy | AppError::Storage(_) => {
+                ErrorCategory::Storage
+            }
+            AppError::DatabaseLocked | AppError::WrongPassphrase | AppError::Unauthorized(_) => {
+                ErrorCategory::Auth
+            }
+            AppError::InvalidInput(_) => ErrorCategory::Validation,
+            AppError::NotFound(_) | AppError::ModelNotFound(_) => ErrorCategory::NotFound,
+            AppError::Conflict(_) => ErrorCategory::Conflict,
+            AppError::OllamaOffline(_)
+            | AppError::Network(_)
+            | AppError::Timeout(_)
+            | AppError::Service { .. } => ErrorCategory::Network,
+            AppError::Internal(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// Whether the same request may succeed if simply tried again later
+    pub fn retryable(&self) -> bool {
+        match self {
+            AppError::DatabaseUnavailable(_)
+            | AppError::DatabaseBusy
+            | AppError::OllamaOffline(_)
+            | AppError::Network(_)
+            | AppError::Timeout(_) => true,
+            AppError::Service { retryable, .. } => *retryable,
+            _ => false,
+        }
+    }
+
+    /// Classify a request to `service` (e.g. "the weather service") that
+    /// got no usable response
+    pub fn request(service: &str, e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            AppError::Timeout(format!("{} did not respond in time", capitalize(service)))
+        } else if e.is_connect() {
+            AppError::Network(format!("Could not connect to {}", service))
+        } else if e.is_decode() {
+            AppError::Service {
+                message: format!("Unexpected response from {}: {}", service, e),
+                retryable: false,
+            }
+        } else {
+            AppError::Network(format!("Request to {} failed: {}", service, e))
+        }
+    }
+
+    /// Classify an unsuccessful HTTP status returned by `service`
+    pub fn response(service: &str, status: StatusCode, body: &str) -> Self {
+        let detail = if body.trim().is_empty() {
+            status.to_string()
+        } else {
+            format!("{}: {}", status, body.trim())
+        };
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AppError::Unauthorized(format!(
+                "{} rejected the request ({})",
+                capitalize(service),
+                detail
+            )),
+            StatusCode::NOT_FOUND => {
+                AppError::NotFound(format!("Not found on {} ({})", service, detail))
+            }
+            _ => AppError::Service {
+                message: format!("{} returned an error ({})", capitalize(service), detail),
+                retryable: status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            },
+        }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::DatabaseUnavailable(message) => write!(f, "{}", message),
+            AppError::DatabaseLocked => write!(f, "Database is locked"),
+            AppError::DatabaseBusy => write!(f, "Database is busy, please try again"),
+            AppError::WrongPassphrase => write!(f, "Incorrect passphrase"),
+            AppError::Conflict(_) => write!(f, "Note was changed since it was opened"),
+            AppError::OllamaOffline(url) => write!(f, "Ollama is not running at {}", url),
+            AppError::ModelNotFound(model) => {
+                write!(f, "Model '{}' is not installed in Ollama", model)
+            }
+            AppError::Storage(message)
+            | AppError::InvalidInput(message)
+            | AppError::NotFound(message)
+            | AppError::Network(message)
+            | AppError::Timeout(message)
+            | AppError::Unauthorized(message)
+            | AppError::Service { message, .. }
+            | AppError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ConflictDetails<'a> {
+            current: &'a Note,
+        }
+
+        let mut state = serializer.serialize_struct("AppError", 5)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("category", &self.category())?;
+        state.serialize_field("retryable", &self.retryable())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            AppError::Conflict(current) => {
+                state.serialize_field("details", &ConflictDetails { current })?
+            }
+            _ => state.skip_field("details")?,
+        }
+        state.end()
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::RowNotFound => AppError::NotFound("Record not found".to_string()),
+            sqlx::Error::PoolTimedOut => AppError::DatabaseBusy,
+            // SQLITE_BUSY and SQLITE_LOCKED, including their extended codes
+            sqlx::Error::Database(db)
+                if db
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6)) =>
+            {
+                AppError::DatabaseBusy
+            }
+            _ => AppError::Storage(format!("Database error: {}", e)),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Storage(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Storage(format!("Invalid JSON: {}", e))
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(e: zip::result::ZipError) -> Self {
+        AppError::Storage(format!("Archive error: {}", e))
+    }
+}
@@ -0,0 +1,351 @@
+use crate::commands::Note;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use crate::notes::{self, NoteCursor};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Notes fetched from the database per round trip while exporting
+const EXPORT_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    /// A `YYYY/MM/YYYY-MM-DD.md` tree inside the target folder
+    Markdown,
+    /// A single self-contained HTML file
+    Html,
+    /// The Markdown tree packed into a zip archive
+    Zip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Folder for `markdown`, file path for `html` and `zip`
+    pub path: String,
+    /// Inclusive lower bound on the note date (YYYY-MM-DD)
+    pub from_date: Option<String>,
+    /// Inclusive upper bound on the note date (YYYY-MM-DD)
+    pub to_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSummary {
+    pub path: String,
+    pub notes: u32,
+    pub days: u32,
+    /// Notes left out of a Markdown or zip export because their date is not
+    /// a valid YYYY-MM-DD date to name a file after
+    pub skipped: u32,
+}
+
+#[derive(Serialize)]
+struct DayFrontMatter<'a> {
+    date: &'a str,
+    notes: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    created: &'a str,
+    updated: &'a str,
+}
+
+/// Local wall-clock time a note was written, e.g. "09:41"
+fn local_time(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|ts| ts.with_timezone(&Local).format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn note_tags(note: &Note) -> &[String] {
+    note.tags.as_deref().unwrap_or(&[])
+}
+
+/// `YYYY/MM/YYYY-MM-DD.md` for a day, or None if the stored date is not a
+/// real date. Imported or legacy rows may hold anything, and only a parsed
+/// date is safe to turn into a path.
+fn day_file_path(date: &str) -> Option<PathBuf> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(
+        Path::new(&date.format("%Y").to_string())
+            .join(date.format("%m").to_string())
+            .join(format!("{}.md", date.format("%Y-%m-%d"))),
+    )
+}
+
+fn render_markdown_day(date: &str, notes: &[Note]) -> Result<String, AppError> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in notes.iter().flat_map(note_tags) {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.clone());
+        }
+    }
+
+    let front_matter = DayFrontMatter {
+        date,
+        notes: notes.len(),
+        tags,
+        created: notes
+            .iter()
+            .map(|n| n.created_at.as_str())
+            .min()
+            .unwrap_or(""),
+        updated: notes
+            .iter()
+            .map(|n| n.updated_at.as_str())
+            .max()
+            .unwrap_or(""),
+    };
+    let yaml =
+        serde_yaml::to_string(&front_matter).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let mut out = format!("---\n{}---\n", yaml);
+    for note in notes {
+        let heading = match &note.title {
+            Some(title) if !title.trim().is_empty() => {
+                format!("{} · {}", local_time(&note.created_at), title.trim())
+            }
+            _ => local_time(&note.created_at),
+        };
+        out.push_str(&format!("\n## {}\n\n", heading));
+        out.push_str(note.content.trim_end());
+        out.push('\n');
+        if !note_tags(note).is_empty() {
+            out.push_str(&format!("\n*Tags: {}*\n", note_tags(note).join(", ")));
+        }
+    }
+
+    Ok(out)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>HeLpER Journal</title>
+<style>
+  body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif; max-width: 46rem; margin: 2rem auto; padding: 0 1rem; color: #222; line-height: 1.55; }
+  section { margin-bottom: 2.5rem; }
+  h2 { border-bottom: 1px solid #ddd; padding-bottom: .25rem; }
+  article { margin: 1rem 0 1.5rem; }
+  article h3 { margin: 0 0 .25rem; font-size: 1rem; }
+  time { color: #777; font-size: .85rem; }
+  .content { white-space: pre-wrap; }
+  .tags { color: #557; font-size: .85rem; }
+</style>
+</head>
+<body>
+<h1>HeLpER Journal</h1>
+"#;
+
+const HTML_FOOTER: &str = "</body>\n</html>\n";
+
+fn render_html_day(date: &str, notes: &[Note]) -> String {
+    let mut out = format!("<section id=\"{0}\">\n<h2>{0}</h2>\n", escape_html(date));
+    for note in notes {
+        out.push_str("<article>\n");
+        out.push_str(&format!(
+            "<time datetime=\"{}\">{}</time>\n",
+            escape_html(&note.created_at),
+            local_time(&note.created_at)
+        ));
+        if let Some(title) = note.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            out.push_str(&format!("<h3>{}</h3>\n", escape_html(title)));
+        }
+        out.push_str(&format!(
+            "<div class=\"content\">{}</div>\n",
+            escape_html(note.content.trim_end())
+        ));
+        if !note_tags(note).is_empty() {
+            out.push_str(&format!(
+                "<p class=\"tags\">Tags: {}</p>\n",
+                escape_html(&note_tags(note).join(", "))
+            ));
+        }
+        out.push_str("</article>\n");
+    }
+    out.push_str("</section>\n");
+    out
+}
+
+/// Where the rendered days go
+enum Sink {
+    Markdown(PathBuf),
+    Html(BufWriter<File>),
+    Zip(Box<zip::ZipWriter<File>>),
+}
+
+impl Sink {
+    fn open(format: ExportFormat, path: &Path) -> Result<Self, AppError> {
+        let create_file = |path: &Path| {
+            File::create(path).map_err(|e| {
+                AppError::Storage(format!("Failed to create {}: {}", path.display(), e))
+            })
+        };
+
+        match format {
+            ExportFormat::Markdown => {
+                std::fs::create_dir_all(path).map_err(|e| {
+                    AppError::Storage(format!("Failed to create export folder: {}", e))
+                })?;
+                Ok(Sink::Markdown(path.to_path_buf()))
+            }
+            ExportFormat::Html => {
+                let mut writer = BufWriter::new(create_file(path)?);
+                writer.write_all(HTML_HEADER.as_bytes())?;
+                Ok(Sink::Html(writer))
+            }
+            ExportFormat::Zip => Ok(Sink::Zip(Box::new(zip::ZipWriter::new(create_file(path)?)))),
+        }
+    }
+
+    /// Write one day's notes, returning false if the day was skipped
+    /// because its date cannot name a file
+    fn write_day(&mut self, date: &str, notes: &[Note]) -> Result<bool, AppError> {
+        match self {
+            Sink::Markdown(root) => {
+                let Some(relative) = day_file_path(date) else {
+                    return Ok(false);
+                };
+                let path = root.join(relative);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, render_markdown_day(date, notes)?).map_err(|e| {
+                    AppError::Storage(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+            }
+            Sink::Html(writer) => writer.write_all(render_html_day(date, notes).as_bytes())?,
+            Sink::Zip(archive) => {
+                let Some(relative) = day_file_path(date) else {
+                    return Ok(false);
+                };
+                // Zip entry names always use forward slashes
+                let name = relative.to_string_lossy().replace('\\', "/");
+                archive.start_file(name, zip::write::SimpleFileOptions::default())?;
+                archive.write_all(render_markdown_day(date, notes)?.as_bytes())?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn finish(self) -> Result<(), AppError> {
+        match self {
+            Sink::Markdown(_) => Ok(()),
+            Sink::Html(mut writer) => {
+                writer.write_all(HTML_FOOTER.as_bytes())?;
+                writer.flush().map_err(AppError::from)
+            }
+            Sink::Zip(archive) => archive.finish().map(|_| ()).map_err(AppError::from),
+        }
+    }
+}
+
+/// Feed notes to the sink one day at a time, paging through the database so
+/// only a page of notes (plus the day being assembled) is ever in memory.
+async fn write_days(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+    sink: &mut Sink,
+    summary: &mut ExportSummary,
+) -> Result<(), AppError> {
+    let mut cursor: Option<NoteCursor> = None;
+    let mut day: Vec<Note> = Vec::new();
+
+    loop {
+        let page = notes::notes_for_range(
+            pool,
+            start,
+            end,
+            cursor.as_ref(),
+            Some(EXPORT_PAGE_SIZE),
+            None,
+        )
+        .await?;
+
+        for note in page.notes {
+            if day.first().map(|n| n.date != note.date).unwrap_or(false) {
+                write_day(sink, &day, summary)?;
+                day.clear();
+            }
+            day.push(note);
+        }
+
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    if !day.is_empty() {
+        write_day(sink, &day, summary)?;
+    }
+
+    Ok(())
+}
+
+fn write_day(sink: &mut Sink, day: &[Note], summary: &mut ExportSummary) -> Result<(), AppError> {
+    if sink.write_day(&day[0].date, day)? {
+        summary.notes += day.len() as u32;
+        summary.days += 1;
+    } else {
+        eprintln!(
+            "Skipped exporting notes with invalid date {:?}",
+            day[0].date
+        );
+        summary.skipped += day.len() as u32;
+    }
+    Ok(())
+}
+
+/// Export all notes, or those in the given date range, straight from the database
+pub async fn export_notes(
+    pool: &SqlitePool,
+    options: &ExportOptions,
+) -> Result<ExportSummary, AppError> {
+    let path = Path::new(&options.path);
+    if !path.is_absolute() {
+        return Err(AppError::InvalidInput(
+            "Export path must be an absolute path".to_string(),
+        ));
+    }
+
+    let start = options.from_date.as_deref().unwrap_or("0000-01-01");
+    let end = options.to_date.as_deref().unwrap_or("9999-12-31");
+    let mut summary = ExportSummary {
+        path: options.path.clone(),
+        notes: 0,
+        days: 0,
+        skipped: 0,
+    };
+
+    let mut sink = Sink::open(options.format, path)?;
+    let result = match write_days(pool, start, end, &mut sink, &mut summary).await {
+        Ok(()) => sink.finish(),
+        Err(e) => {
+            drop(sink);
+            Err(e)
+        }
+    };
+
+    // Don't leave a truncated single-file export behind
+    if result.is_err() && options.format != ExportFormat::Markdown {
+        let _ = std::fs::remove_file(path);
+    }
+
+    result.map(|_| summary)
+}
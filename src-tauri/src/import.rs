@@ -0,0 +1,651 @@
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use crate::links;
+use crate::tags;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+/// Supported export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportFormat {
+    /// Day One JSON export (`Journal.json`, or the zip containing it)
+    DayOne,
+    /// Journey JSON export (one JSON file per entry, or the zip containing them)
+    Journey,
+    /// A folder of Markdown/text files named or front-mattered with their date
+    Markdown,
+}
+
+impl ImportFormat {
+    /// Identifier stored in `note_imports.source`
+    fn source(self) -> &'static str {
+        match self {
+            ImportFormat::DayOne => "day_one",
+            ImportFormat::Journey => "journey",
+            ImportFormat::Markdown => "markdown",
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ImportFormat::DayOne | ImportFormat::Journey => &["json"],
+            ImportFormat::Markdown => &["md", "markdown", "txt"],
+        }
+    }
+}
+
+/// What happened to the entries of a single file in the import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportFileSummary {
+    /// Path relative to the imported folder or archive
+    pub file: String,
+    pub imported: u32,
+    /// Entries already imported earlier, or without any text
+    pub skipped: u32,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub files: Vec<ImportFileSummary>,
+    pub imported: u32,
+    pub skipped: u32,
+    /// Number of entries or files that could not be imported
+    pub failed: u32,
+}
+
+/// A journal entry parsed from an export, ready to become a note
+struct ImportedEntry {
+    /// Stable id from the source app, used to recognise re-imports
+    external_id: String,
+    date: String,
+    title: Option<String>,
+    content: String,
+    created_at: String,
+    updated_at: String,
+    tags: Vec<String>,
+}
+
+#[derive(Default)]
+struct ParsedFile {
+    entries: Vec<ImportedEntry>,
+    errors: Vec<String>,
+}
+
+struct SourceFile {
+    name: String,
+    contents: Result<String, AppError>,
+}
+
+fn to_timestamp(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// The journal date of a moment, in the entry's own time zone when known
+fn journal_date(ts: DateTime<Utc>, time_zone: Option<&str>) -> String {
+    match time_zone.and_then(|tz| tz.parse::<chrono_tz::Tz>().ok()) {
+        Some(tz) => ts.with_timezone(&tz).format("%Y-%m-%d").to_string(),
+        None => ts.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Parse an RFC 3339 timestamp, or a naive date-time in local time
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Some(ts.with_timezone(&Utc));
+    }
+
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// Use a leading Markdown heading as the note title
+fn split_title(text: &str) -> (Option<String>, String) {
+    let trimmed = text.trim_start();
+    if let Some(first_line) = trimmed.lines().next() {
+        let heading = first_line.trim_start_matches('#');
+        if heading.len() < first_line.len()
+            && heading.starts_with(' ')
+            && !heading.trim().is_empty()
+        {
+            let body = trimmed[first_line.len()..].trim().to_string();
+            return (Some(heading.trim().to_string()), body);
+        }
+    }
+    (None, text.trim().to_string())
+}
+
+/// Day One escapes Markdown punctuation in its exports (`\.`, `\-`, ...)
+fn unescape_markdown(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if next.is_ascii_punctuation() {
+                    result.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Rough conversion of Journey's rich-text entries into plain text
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(offset) => start + offset,
+            None => break,
+        };
+        let tag = rest[start + 1..end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if matches!(
+            tag.as_str(),
+            "br" | "p" | "div" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote"
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    // Collapse the blank lines left behind by nested block elements
+    let mut result = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    result.trim().to_string()
+}
+
+#[derive(Deserialize)]
+struct DayOneExport {
+    entries: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    uuid: String,
+    creation_date: String,
+    modified_date: Option<String>,
+    time_zone: Option<String>,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn parse_day_one(contents: &str) -> Result<ParsedFile, AppError> {
+    let export: DayOneExport = serde_json::from_str(contents)
+        .map_err(|e| AppError::InvalidInput(format!("Not a Day One export: {}", e)))?;
+    let mut parsed = ParsedFile::default();
+
+    for (index, value) in export.entries.into_iter().enumerate() {
+        let entry: DayOneEntry = match serde_json::from_value(value) {
+            Ok(entry) => entry,
+            Err(e) => {
+                parsed.errors.push(format!("Entry {}: {}", index + 1, e));
+                continue;
+            }
+        };
+        let created = match parse_timestamp(&entry.creation_date) {
+            Some(ts) => ts,
+            None => {
+                parsed.errors.push(format!(
+                    "Entry {}: invalid creation date '{}'",
+                    index + 1,
+                    entry.creation_date
+                ));
+                continue;
+            }
+        };
+        let updated = entry
+            .modified_date
+            .as_deref()
+            .and_then(parse_timestamp)
+            .unwrap_or(created);
+        let (title, content) = split_title(&unescape_markdown(&entry.text));
+
+        parsed.entries.push(ImportedEntry {
+            external_id: entry.uuid,
+            date: journal_date(created, entry.time_zone.as_deref()),
+            title,
+            content,
+            created_at: to_timestamp(created),
+            updated_at: to_timestamp(updated),
+            tags: entry.tags,
+        });
+    }
+
+    Ok(parsed)
+}
+
+#[derive(Deserialize)]
+struct JourneyEntry {
+    id: String,
+    #[serde(default)]
+    text: String,
+    /// Milliseconds since the Unix epoch
+    date_journal: i64,
+    date_modified: Option<i64>,
+    timezone: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Journey writes one entry per file, but some versions bundle them in an array
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JourneyFile {
+    Single(JourneyEntry),
+    Multiple(Vec<JourneyEntry>),
+}
+
+fn parse_journey(contents: &str) -> Result<ParsedFile, AppError> {
+    let entries = match serde_json::from_str(contents)
+        .map_err(|e| AppError::InvalidInput(format!("Not a Journey export: {}", e)))?
+    {
+        JourneyFile::Single(entry) => vec![entry],
+        JourneyFile::Multiple(entries) => entries,
+    };
+    let mut parsed = ParsedFile::default();
+
+    for entry in entries {
+        let created = match Utc.timestamp_millis_opt(entry.date_journal).single() {
+            Some(ts) => ts,
+            None => {
+                parsed
+                    .errors
+                    .push(format!("Entry {}: invalid journal date", entry.id));
+                continue;
+            }
+        };
+        let updated = entry
+            .date_modified
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+            .unwrap_or(created);
+        let text = match entry.kind.as_deref() {
+            Some("html") => html_to_text(&entry.text),
+            _ => entry.text,
+        };
+        let (title, content) = split_title(&text);
+
+        parsed.entries.push(ImportedEntry {
+            external_id: entry.id,
+            date: journal_date(created, entry.timezone.as_deref()),
+            title,
+            content,
+            created_at: to_timestamp(created),
+            updated_at: to_timestamp(updated),
+            tags: entry.tags,
+        });
+    }
+
+    Ok(parsed)
+}
+
+/// Split a leading `---` delimited YAML block off a Markdown document
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let text = text.trim_start_matches('\u{feff}');
+    let rest = match text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, text),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+fn yaml_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_markdown(name: &str, contents: &str) -> Result<ParsedFile, AppError> {
+    let (front_matter, body) = split_front_matter(contents);
+    let meta = match front_matter {
+        Some(yaml) => serde_yaml::from_str::<serde_yaml::Mapping>(yaml)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid front matter: {}", e)))?,
+        None => serde_yaml::Mapping::new(),
+    };
+    let field = |key: &str| meta.get(key).and_then(yaml_string);
+
+    let file_stem = Path::new(name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let date = field("date")
+        .and_then(|d| NaiveDate::parse_from_str(d.get(..10).unwrap_or(&d), "%Y-%m-%d").ok())
+        .or_else(|| {
+            file_stem
+                .get(..10)
+                .and_then(|prefix| NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok())
+        })
+        .ok_or_else(|| {
+            AppError::InvalidInput(
+                "No date in front matter or file name (expected YYYY-MM-DD)".to_string(),
+            )
+        })?;
+
+    // Without explicit timestamps the entry is placed at the start of its day
+    let start_of_day = Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .map(|ts| ts.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+    let created = field("created")
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or(start_of_day);
+    let updated = field("updated")
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or(created);
+
+    let tags = match meta.get("tags") {
+        Some(serde_yaml::Value::Sequence(items)) => items.iter().filter_map(yaml_string).collect(),
+        Some(value) => yaml_string(value)
+            .map(|s| s.split(',').map(|t| t.to_string()).collect())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let (title, content) = match field("title") {
+        Some(title) => (Some(title), body.trim().to_string()),
+        None => split_title(body),
+    };
+
+    Ok(ParsedFile {
+        entries: vec![ImportedEntry {
+            external_id: field("id").unwrap_or_else(|| name.to_string()),
+            date: date.format("%Y-%m-%d").to_string(),
+            title,
+            content,
+            created_at: to_timestamp(created),
+            updated_at: to_timestamp(updated),
+            tags,
+        }],
+        errors: Vec::new(),
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .map(|ext| extensions.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    extensions: &[&str],
+    files: &mut Vec<SourceFile>,
+) -> Result<(), AppError> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| AppError::Storage(format!("Failed to read {}: {}", dir.display(), e)))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            !path
+                .file_name()
+                .map(|n| n.to_string_lossy().starts_with('.'))
+                .unwrap_or(false)
+        })
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk_dir(root, &path, extensions, files)?;
+        } else if has_extension(&path, extensions) {
+            files.push(SourceFile {
+                name: path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string(),
+                contents: std::fs::read_to_string(&path).map_err(AppError::from),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn read_zip(path: &Path, extensions: &[&str]) -> Result<Vec<SourceFile>, AppError> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| AppError::Storage(format!("Failed to open archive: {}", e)))?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut files = Vec::new();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        // Skip folders and the resource forks macOS adds when zipping
+        if entry.is_dir()
+            || name.starts_with("__MACOSX/")
+            || !has_extension(Path::new(&name), extensions)
+        {
+            continue;
+        }
+
+        let mut contents = String::new();
+        let contents = entry
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+            .map_err(AppError::from);
+        files.push(SourceFile { name, contents });
+    }
+
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+/// Collect the candidate files from a single file, a folder or a zip archive
+fn collect_files(path: &Path, format: ImportFormat) -> Result<Vec<SourceFile>, AppError> {
+    let extensions = format.extensions();
+
+    if path.is_dir() {
+        let mut files = Vec::new();
+        walk_dir(path, path, extensions, &mut files)?;
+        Ok(files)
+    } else if has_extension(path, &["zip"]) {
+        read_zip(path, extensions)
+    } else if path.is_file() {
+        Ok(vec![SourceFile {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            contents: std::fs::read_to_string(path).map_err(AppError::from),
+        }])
+    } else {
+        Err(AppError::NotFound(format!(
+            "Import path not found: {}",
+            path.display()
+        )))
+    }
+}
+
+/// Insert one file's entries in a single transaction, skipping re-imports
+async fn write_entries(
+    pool: &SqlitePool,
+    source: &str,
+    entries: Vec<ImportedEntry>,
+    summary: &mut ImportFileSummary,
+) -> Result<(), AppError> {
+    let imported_at = to_timestamp(Utc::now());
+    let mut tx = pool.begin().await?;
+    let (mut imported, mut skipped) = (0, 0);
+
+    for entry in entries {
+        // A note exported from HeLpER keeps its id, so re-importing it is also a duplicate
+        let exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM note_imports WHERE source = ? AND external_id = ?)
+                OR EXISTS(SELECT 1 FROM notes WHERE id = ?)
+            "#,
+        )
+        .bind(source)
+        .bind(&entry.external_id)
+        .bind(&entry.external_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if exists || (entry.content.is_empty() && entry.title.is_none()) {
+            skipped += 1;
+            continue;
+        }
+
+        let note_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO notes (id, date, title, content, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&note_id)
+        .bind(&entry.date)
+        .bind(crypto::encrypt_optional(&entry.title)?)
+        .bind(crypto::encrypt_text(&entry.content)?)
+        .bind(&entry.created_at)
+        .bind(&entry.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tags::set_note_tags(&mut tx, &note_id, &entry.tags).await?;
+        links::index_note(&mut tx, &note_id, &entry.content).await?;
+        links::title_changed(&mut tx, &note_id, None, entry.title.as_deref()).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO note_imports (source, external_id, note_id, imported_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(source)
+        .bind(&entry.external_id)
+        .bind(&note_id)
+        .bind(&imported_at)
+        .execute(&mut *tx)
+        .await?;
+
+        imported += 1;
+    }
+
+    tx.commit().await?;
+
+    summary.imported += imported;
+    summary.skipped += skipped;
+    Ok(())
+}
+
+/// Import every entry found at `path`.
+///
+/// Each file is imported in its own transaction, so a problem in one file
+/// never leaves it half imported and does not stop the others. Entries that
+/// were imported before are recognised by their source id and skipped.
+pub async fn import_notes(
+    pool: &SqlitePool,
+    format: ImportFormat,
+    path: &Path,
+) -> Result<ImportSummary, AppError> {
+    let mut summary = ImportSummary::default();
+
+    for file in collect_files(path, format)? {
+        let mut file_summary = ImportFileSummary {
+            file: file.name.clone(),
+            ..Default::default()
+        };
+
+        let parsed = file.contents.and_then(|contents| match format {
+            ImportFormat::DayOne => parse_day_one(&contents),
+            ImportFormat::Journey => parse_journey(&contents),
+            ImportFormat::Markdown => parse_markdown(&file.name, &contents),
+        });
+
+        match parsed {
+            Ok(parsed) => {
+                summary.failed += parsed.errors.len() as u32;
+                file_summary.errors = parsed.errors;
+                if let Err(e) =
+                    write_entries(pool, format.source(), parsed.entries, &mut file_summary).await
+                {
+                    file_summary.errors.push(e.to_string());
+                    summary.failed += 1;
+                }
+            }
+            Err(e) => {
+                file_summary.errors.push(e.to_string());
+                summary.failed += 1;
+            }
+        }
+
+        summary.imported += file_summary.imported;
+        summary.skipped += file_summary.skipped;
+        summary.files.push(file_summary);
+    }
+
+    // Imported notes may link to each other (and existing notes to them),
+    // which is only resolvable once they are all in
+    if summary.imported > 0 {
+        crate::links::rebuild(pool).await?;
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,591 @@
+use crate::commands::{ChatMessage, Note};
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::embeddings;
+use crate::error::AppError;
+use crate::notes;
+use crate::ollama::{self, ChatStats};
+use crate::settings::AiSettings;
+use chrono::{Datelike, Days, Months, NaiveDate, TimeDelta, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Entries handed to the model with each question
+const MAX_SOURCES: usize = 8;
+/// Candidates taken from each retrieval method before they are combined
+const CANDIDATES_PER_METHOD: i64 = 20;
+/// Long entries are cut off so several fit in the model's context
+const MAX_ENTRY_CHARS: usize = 1500;
+/// Damping constant of reciprocal rank fusion; higher flattens the ranking
+const RANK_FUSION_K: f64 = 60.0;
+
+/// Words too common to be worth a keyword search
+const STOPWORDS: &str = "\
+    about after again all and any are been before but can did does doing for from had has \
+    have how last many much not our over said she should that the their them then there \
+    these they this those through time was week were what when where which while who why \
+    will with would you your month year today yesterday ago days times ever most recently \
+    tell remind journal write wrote written mention mentioned";
+
+/// A journal entry an answer was based on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalSource {
+    /// Number the entry was given in the prompt, as cited in the answer, e.g. [2]
+    pub number: usize,
+    pub note_id: String,
+    pub date: String,
+    pub title: Option<String>,
+    /// Whether the answer cites this entry
+    pub cited: bool,
+}
+
+fn month_from_name(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    MONTHS
+        .iter()
+        .position(|month| *month == word || (word.len() >= 3 && month.starts_with(word)))
+        .map(|index| index as u32 + 1)
+}
+
+fn weekday_from_name(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" => Some(Weekday::Mon),
+        "tuesday" => Some(Weekday::Tue),
+        "wednesday" => Some(Weekday::Wed),
+        "thursday" => Some(Weekday::Thu),
+        "friday" => Some(Weekday::Fri),
+        "saturday" => Some(Weekday::Sat),
+        "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_year(word: &str) -> Option<i32> {
+    word.parse()
+        .ok()
+        .filter(|year| (1900..=2100).contains(year))
+}
+
+fn month_range(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((first, next.pred_opt()?))
+}
+
+fn year_range(year: i32) -> Option<(NaiveDate, NaiveDate)> {
+    Some((
+        NaiveDate::from_ymd_opt(year, 1, 1)?,
+        NaiveDate::from_ymd_opt(year, 12, 31)?,
+    ))
+}
+
+fn week_start(date: NaiveDate) -> Option<NaiveDate> {
+    date.checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+}
+
+/// Monday to Sunday of the week containing `date`
+fn week_range(date: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let start = week_start(date)?;
+    Some((start, start.checked_add_days(Days::new(6))?))
+}
+
+/// The dates a question asks about, as an inclusive range.
+///
+/// Understands ISO dates, "today", "yesterday", "this/last week|month|year",
+/// "past week|month", "N days|weeks|months ago", weekday names ("last
+/// Tuesday"), months ("in March", "March 2025") and years ("in 2024").
+/// Month names need a preposition or a year next to them, so "may" in "may I
+/// ask" is not taken as a date.
+pub fn date_range(question: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let lower = question.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    for (i, &word) in words.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|p| words[p]);
+        let next = words.get(i + 1).copied();
+
+        if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            return Some((date, date));
+        }
+
+        match (previous, word) {
+            (_, "today") => return Some((today, today)),
+            (_, "yesterday") => {
+                let day = today.pred_opt()?;
+                return Some((day, day));
+            }
+            (Some("this"), "week") => return Some((week_start(today)?, today)),
+            (Some("last"), "week") => return week_range(today.checked_sub_days(Days::new(7))?),
+            (Some("past"), "week") => return Some((today.checked_sub_days(Days::new(6))?, today)),
+            (Some("this"), "month") => return Some((today.with_day(1)?, today)),
+            (Some("last"), "month") => {
+                let last_month = today.with_day(1)?.pred_opt()?;
+                return month_range(last_month.year(), last_month.month());
+            }
+            (Some("past"), "month") => {
+                return Some((today.checked_sub_days(Days::new(29))?, today))
+            }
+            (Some("this"), "year") => return Some((today.with_ordinal(1)?, today)),
+            (Some("last"), "year") => return year_range(today.year() - 1),
+            _ => {}
+        }
+
+        // The count comes straight from the question, so anything too far
+        // back to be a date gives up instead of overflowing
+        if let (Ok(count), Some(unit), Some("ago")) =
+            (word.parse::<u32>(), next, words.get(i + 2).copied())
+        {
+            match unit {
+                "day" | "days" => {
+                    let day = today.checked_sub_days(Days::new(count as u64))?;
+                    return Some((day, day));
+                }
+                "week" | "weeks" => {
+                    let back = TimeDelta::try_weeks(count as i64)?;
+                    return week_range(today.checked_sub_signed(back)?);
+                }
+                "month" | "months" => {
+                    let month = today.with_day(1)?.checked_sub_months(Months::new(count))?;
+                    return month_range(month.year(), month.month());
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(weekday) = weekday_from_name(word) {
+            // The most recent such day before today
+            let offset = today.weekday().num_days_from_monday() as i64
+                - weekday.num_days_from_monday() as i64;
+            let back = ((offset - 1).rem_euclid(7) + 1) as u64;
+            let day = today.checked_sub_days(Days::new(back))?;
+            return Some((day, day));
+        }
+
+        if let Some(month) = month_from_name(word) {
+            let year = next.and_then(parse_year);
+            let has_preposition = matches!(
+                previous,
+                Some("in" | "during" | "of" | "last" | "early" | "late")
+            );
+            if year.is_some() || has_preposition {
+                // Without a year, the most recent such month
+                let year = year.unwrap_or(if month <= today.month() {
+                    today.year()
+                } else {
+                    today.year() - 1
+                });
+                return month_range(year, month);
+            }
+        }
+
+        if let Some(year) = parse_year(word) {
+            if matches!(previous, Some("in" | "during")) {
+                return year_range(year);
+            }
+        }
+    }
+
+    None
+}
+
+/// Words of the question worth searching for
+fn keywords(question: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    question
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .filter(|word| {
+            !STOPWORDS
+                .split_whitespace()
+                .any(|stopword| stopword == *word)
+        })
+        .filter(|word| month_from_name(word).is_none() && weekday_from_name(word).is_none())
+        .filter(|word| seen.insert(word.to_string()))
+        .map(String::from)
+        .collect()
+}
+
+async fn notes_in_range(
+    pool: &SqlitePool,
+    (from, to): (NaiveDate, NaiveDate),
+) -> Result<Vec<String>, AppError> {
+    sqlx::query_scalar(
+        r#"
+        SELECT id FROM notes
+        WHERE deleted_at IS NULL AND date BETWEEN ? AND ?
+        ORDER BY date DESC, created_at DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(from.format("%Y-%m-%d").to_string())
+    .bind(to.format("%Y-%m-%d").to_string())
+    .bind(CANDIDATES_PER_METHOD)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Notes matching any keyword, best first
+async fn notes_by_keywords(
+    pool: &SqlitePool,
+    words: &[String],
+    range: Option<(NaiveDate, NaiveDate)>,
+) -> Result<Vec<String>, AppError> {
+    // Only ciphertext is indexed once encryption is enabled
+    if words.is_empty() || crypto::is_active() {
+        return Ok(Vec::new());
+    }
+
+    let match_query = words
+        .iter()
+        .map(|word| format!("\"{}\"*", word))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let from = range.map(|(from, _)| from.format("%Y-%m-%d").to_string());
+    let to = range.map(|(_, to)| to.format("%Y-%m-%d").to_string());
+
+    sqlx::query_scalar(
+        r#"
+        SELECT n.id
+        FROM notes_fts
+        JOIN notes n ON n.rowid = notes_fts.rowid
+        WHERE notes_fts MATCH ?
+          AND n.deleted_at IS NULL
+          AND (? IS NULL OR n.date >= ?)
+          AND (? IS NULL OR n.date <= ?)
+        ORDER BY bm25(notes_fts, 10.0, 1.0), n.date DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(match_query)
+    .bind(&from)
+    .bind(&from)
+    .bind(&to)
+    .bind(&to)
+    .bind(CANDIDATES_PER_METHOD)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| AppError::Storage(format!("Search failed: {}", e)))
+}
+
+/// Notes similar in meaning, when semantic search is turned on. A failure
+/// here (e.g. the embedding model is missing) leaves the other methods to
+/// answer rather than failing the question.
+async fn notes_by_similarity(
+    pool: &SqlitePool,
+    config: &AiSettings,
+    question: &str,
+    range: Option<(NaiveDate, NaiveDate)>,
+) -> Vec<String> {
+    if !config.semantic_search {
+        return Vec::new();
+    }
+
+    match embeddings::semantic_search(pool, question, Some(CANDIDATES_PER_METHOD as u32)).await {
+        Ok(results) => results
+            .hits
+            .into_iter()
+            .filter(|hit| match range {
+                Some((from, to)) => NaiveDate::parse_from_str(&hit.note.date, "%Y-%m-%d")
+                    .map(|date| date >= from && date <= to)
+                    .unwrap_or(false),
+                None => true,
+            })
+            .map(|hit| hit.note.id)
+            .collect(),
+        Err(e) => {
+            eprintln!("Semantic search for journal question failed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Combine ranked lists of note ids with reciprocal rank fusion, so a note
+/// found by several methods beats one found by a single method
+fn fuse(lists: &[Vec<String>]) -> Vec<String> {
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    for list in lists {
+        for (rank, id) in list.iter().enumerate() {
+            *scores.entry(id).or_default() += 1.0 / (RANK_FUSION_K + rank as f64 + 1.0);
+        }
+    }
+
+    let mut fused: Vec<(&str, f64)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    fused.into_iter().map(|(id, _)| id.to_string()).collect()
+}
+
+/// Find the journal entries most relevant to a question by the dates it
+/// mentions, its keywords and (when enabled) meaning. Returned oldest first.
+pub async fn retrieve(
+    pool: &SqlitePool,
+    config: &AiSettings,
+    question: &str,
+    today: NaiveDate,
+) -> Result<Vec<Note>, AppError> {
+    let range = date_range(question, today);
+
+    let mut lists = Vec::new();
+    if let Some(range) = range {
+        lists.push(notes_in_range(pool, range).await?);
+    }
+    lists.push(notes_by_keywords(pool, &keywords(question), range).await?);
+    lists.push(notes_by_similarity(pool, config, question, range).await);
+
+    let mut sources = Vec::new();
+    for id in fuse(&lists).into_iter().take(MAX_SOURCES) {
+        if let Some(note) = notes::get_note(pool, &id).await? {
+            sources.push(note);
+        }
+    }
+    sources.sort_by(|a, b| (&a.date, &a.created_at).cmp(&(&b.date, &b.created_at)));
+
+    Ok(sources)
+}
+
+fn format_entry(number: usize, note: &Note) -> String {
+    let heading = match note.title.as_deref() {
+        Some(title) if !title.trim().is_empty() => {
+            format!("[{}] {} — {}", number, note.date, title)
+        }
+        _ => format!("[{}] {}", number, note.date),
+    };
+    let content: String = note.content.chars().take(MAX_ENTRY_CHARS).collect();
+    let ellipsis = if content.len() < note.content.len() {
+        " …"
+    } else {
+        ""
+    };
+    format!("{}\n{}{}", heading, content.trim(), ellipsis)
+}
+
+/// The conversation to send to the model: instructions with the numbered
+/// entries, the earlier conversation, then the question
+pub fn build_messages(
+    question: &str,
+    history: Vec<ChatMessage>,
+    sources: &[Note],
+    today: NaiveDate,
+) -> Vec<ChatMessage> {
+    let timestamp = Utc::now().to_rfc3339();
+
+    let mut instructions = format!(
+        "You answer questions about the user's personal journal. Today is {} ({}).\n",
+        today.format("%Y-%m-%d"),
+        today.format("%A")
+    );
+    if sources.is_empty() {
+        instructions.push_str(
+            "No journal entries matched this question. Say that you could not find \
+             anything about it in the journal; do not make anything up.",
+        );
+    } else {
+        instructions.push_str(
+            "Answer using only the journal entries below. Cite the entries you rely on \
+             by their number in square brackets, e.g. [2]. If the entries do not contain \
+             the answer, say so.\n\n",
+        );
+        let entries: Vec<String> = sources
+            .iter()
+            .enumerate()
+            .map(|(i, note)| format_entry(i + 1, note))
+            .collect();
+        instructions.push_str(&entries.join("\n\n"));
+    }
+
+    let mut messages = vec![ChatMessage {
+        role: "system".to_string(),
+        content: instructions,
+        timestamp: timestamp.clone(),
+    }];
+    // Earlier instructions carried entries for earlier questions
+    messages.extend(history.into_iter().filter(|m| m.role != "system"));
+    messages.push(ChatMessage {
+        role: "user".to_string(),
+        content: question.to_string(),
+        timestamp,
+    });
+    messages
+}
+
+/// Entry numbers cited in an answer, e.g. "[2]" or "[1, 3]"
+fn cited_numbers(answer: &str) -> HashSet<usize> {
+    let mut cited = HashSet::new();
+    for part in answer.split('[').skip(1) {
+        let Some(end) = part.find(']') else {
+            continue;
+        };
+        let inner = &part[..end];
+        if inner
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ',' || c == ' ')
+        {
+            cited.extend(
+                inner
+                    .split(',')
+                    .filter_map(|n| n.trim().parse::<usize>().ok()),
+            );
+        }
+    }
+    cited
+}
+
+/// The entries given to the model, marking those the answer cites
+pub fn sources(notes: &[Note], answer: &str) -> Vec<JournalSource> {
+    let cited = cited_numbers(answer);
+    notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| JournalSource {
+            number: i + 1,
+            note_id: note.id.clone(),
+            date: note.date.clone(),
+            title: note.title.clone(),
+            cited: cited.contains(&(i + 1)),
+        })
+        .collect()
+}
+
+/// Answer a question from the retrieved entries, streaming the reply to
+/// `on_chunk`
+pub async fn answer(
+    config: &AiSettings,
+    question: &str,
+    history: Vec<ChatMessage>,
+    sources: &[Note],
+    today: NaiveDate,
+    on_chunk: impl FnMut(&str),
+) -> Result<(ChatMessage, ChatStats), AppError> {
+    let question = question.trim();
+    if question.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Question cannot be empty".to_string(),
+        ));
+    }
+
+    let messages = build_messages(question, history, sources, today);
+    ollama::stream_message(
+        &config.ollama_url,
+        &config.model,
+        messages,
+        config.temperature as f32,
+        config.max_tokens,
+        on_chunk,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()
+    }
+
+    fn range(question: &str) -> Option<(String, String)> {
+        date_range(question, today()).map(|(from, to)| (from.to_string(), to.to_string()))
+    }
+
+    fn days(from: &str, to: &str) -> Option<(String, String)> {
+        Some((from.to_string(), to.to_string()))
+    }
+
+    #[test]
+    fn relative_days_and_weeks() {
+        assert_eq!(
+            range("What did I do today?"),
+            days("2026-10-14", "2026-10-14")
+        );
+        assert_eq!(range("yesterday"), days("2026-10-13", "2026-10-13"));
+        assert_eq!(range("this week"), days("2026-10-12", "2026-10-14"));
+        assert_eq!(range("how was last week"), days("2026-10-05", "2026-10-11"));
+        assert_eq!(range("the past week"), days("2026-10-08", "2026-10-14"));
+        assert_eq!(range("3 days ago"), days("2026-10-11", "2026-10-11"));
+        assert_eq!(range("2 weeks ago"), days("2026-09-28", "2026-10-04"));
+        assert_eq!(range("on Tuesday"), days("2026-10-13", "2026-10-13"));
+        assert_eq!(range("last Wednesday"), days("2026-10-07", "2026-10-07"));
+    }
+
+    #[test]
+    fn months_and_years() {
+        assert_eq!(range("this month"), days("2026-10-01", "2026-10-14"));
+        assert_eq!(range("last month"), days("2026-09-01", "2026-09-30"));
+        assert_eq!(range("2 months ago"), days("2026-08-01", "2026-08-31"));
+        assert_eq!(range("11 months ago"), days("2025-11-01", "2025-11-30"));
+        assert_eq!(range("in March"), days("2026-03-01", "2026-03-31"));
+        assert_eq!(range("in December"), days("2025-12-01", "2025-12-31"));
+        assert_eq!(range("feb 2024"), days("2024-02-01", "2024-02-29"));
+        assert_eq!(range("last year"), days("2025-01-01", "2025-12-31"));
+        assert_eq!(range("during 2023"), days("2023-01-01", "2023-12-31"));
+        assert_eq!(range("on 2026-01-05?"), days("2026-01-05", "2026-01-05"));
+    }
+
+    #[test]
+    fn ignores_words_that_only_look_like_dates() {
+        assert_eq!(range("may I ask about my garden"), None);
+        assert_eq!(range("did I march anywhere"), None);
+        assert_eq!(range("I ran 2024 meters"), None);
+    }
+
+    #[test]
+    fn huge_or_negative_counts_give_no_range() {
+        assert_eq!(range("what did I do 100000000 days ago"), None);
+        assert_eq!(range("4294967295 weeks ago"), None);
+        assert_eq!(range("4294967295 months ago"), None);
+        assert_eq!(range("99999999999999999999 days ago"), None);
+        assert_eq!(range("-3 days ago"), None);
+    }
+
+    #[test]
+    fn keywords_drop_stopwords_dates_and_duplicates() {
+        assert_eq!(
+            keywords("What did I write about the Garden last March? garden, tomatoes!"),
+            vec!["garden", "tomatoes"]
+        );
+        assert_eq!(keywords("on Tuesday in 2024"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn cited_numbers_reads_single_and_grouped_citations() {
+        let cited = cited_numbers("Yes [2], and later [1, 3]. See [link] or [x, 4] or [5");
+        let mut cited: Vec<usize> = cited.into_iter().collect();
+        cited.sort();
+        assert_eq!(cited, vec![1, 2, 3]);
+        assert!(cited_numbers("No citations here").is_empty());
+    }
+
+    #[test]
+    fn fuse_prefers_notes_found_by_several_methods() {
+        let lists = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["c".to_string(), "b".to_string()],
+            Vec::new(),
+        ];
+        assert_eq!(fuse(&lists), vec!["b", "a", "c"]);
+        assert!(fuse(&[]).is_empty());
+    }
+}
@@ -1,7 +1,26 @@
+mod attachments;
+mod backup;
 mod commands;
+mod crypto;
 mod database;
+mod embeddings;
+mod error;
+mod export;
+mod import;
+mod journal_chat;
+mod links;
+mod merge;
 mod natlangchain;
+mod note_context;
+mod notes;
 mod ollama;
+mod revisions;
+mod search;
+mod settings;
+mod stats;
+mod tags;
+mod templates;
+mod trash;
 mod tray;
 mod weather;
 
@@ -19,28 +38,93 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .register_asynchronous_uri_scheme_protocol(
+            attachments::URI_SCHEME,
+            |ctx, request, responder| {
+                let app_handle = ctx.app_handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    responder.respond(attachments::serve(&app_handle, request).await);
+                });
+            },
+        )
         .setup(|app| {
             // Initialize system tray
             tray::create_tray(app.handle())?;
 
-            // Initialize database
+            // Register database state up front so early commands can wait
+            // for it, then open the database in the background
+            app.manage(database::DbPool::default());
+            app.manage(crypto::LockedPool(tokio::sync::Mutex::new(None)));
+            app.manage(ollama::ChatRequests::default());
+
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                if let Err(e) = database::initialize(&app_handle).await {
-                    eprintln!("Failed to initialize database: {}", e);
-                }
+                database::start(&app_handle).await;
             });
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_notes_for_date,
+            commands::get_notes_for_range,
+            commands::get_calendar_summary,
             commands::create_note,
             commands::update_note,
             commands::delete_note,
+            commands::search_notes,
+            commands::semantic_search,
+            commands::list_note_revisions,
+            commands::diff_note_revisions,
+            commands::restore_note_revision,
+            commands::merge_note_content,
+            commands::save_note_context,
+            commands::get_notes_by_context,
+            commands::get_tags,
+            commands::set_note_tags,
+            commands::rename_tag,
+            commands::merge_tags,
+            commands::delete_tag,
+            commands::get_notes_by_tag,
+            commands::get_outgoing_links,
+            commands::get_backlinks,
+            commands::get_unresolved_links,
+            commands::list_trashed_notes,
+            commands::restore_notes,
+            commands::purge_notes,
+            commands::empty_trash,
+            commands::get_writing_stats,
+            commands::list_templates,
+            commands::create_template,
+            commands::update_template,
+            commands::delete_template,
+            commands::apply_template,
+            commands::get_encryption_status,
+            commands::unlock_database,
+            commands::enable_encryption,
+            commands::change_encryption_passphrase,
+            commands::get_settings,
+            commands::update_settings,
+            commands::create_backup,
+            commands::list_backups,
+            commands::restore_backup,
+            commands::add_attachment,
+            commands::list_attachments,
+            commands::remove_attachment,
+            commands::collect_attachment_garbage,
+            commands::import_notes,
+            commands::export_notes,
+            commands::get_database_status,
+            commands::retry_database_initialization,
             commands::check_database_health,
             commands::check_ollama_status,
             commands::send_chat_message,
+            commands::stream_chat_message,
+            commands::cancel_chat_request,
+            commands::ask_journal,
+            commands::list_ollama_models,
+            commands::show_ollama_model,
+            commands::pull_ollama_model,
+            commands::delete_ollama_model,
             commands::get_weather,
             commands::detect_location,
             commands::get_journal_context,
@@ -0,0 +1,496 @@
+use crate::commands::Note;
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Metadata key set once every existing note has been scanned for links
+const LINKS_INDEXED_KEY: &str = "links_indexed";
+
+/// A `[[...]]` link found in a note's content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteLink {
+    /// Link target as written, without any `#heading` or `|label` suffix
+    pub target: String,
+    /// The note the link points to, if a live note has that title
+    pub target_note_id: Option<String>,
+    /// Set instead of `target_note_id` for `[[YYYY-MM-DD]]` links
+    pub target_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnresolvedLink {
+    pub target: String,
+    /// Notes containing a link to the missing target
+    pub source_ids: Vec<String>,
+}
+
+/// Position of one link inside a piece of content
+struct LinkSpan {
+    /// Byte range of the target inside the content
+    target: Range<usize>,
+}
+
+/// Find every `[[target]]`, `[[target|label]]` and `[[target#heading]]` link.
+/// Links cannot span lines or contain brackets.
+fn link_spans(content: &str) -> Vec<LinkSpan> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = content[offset..].find("[[").map(|i| offset + i + 2) {
+        let rest = &content[start..];
+        let end = match rest.find("]]") {
+            Some(end) if !rest[..end].contains(['\n', '[', ']']) => end,
+            _ => {
+                offset = start;
+                continue;
+            }
+        };
+
+        let inner = &rest[..end];
+        let target_len = inner.find(['|', '#']).unwrap_or(inner.len());
+        let target = &inner[..target_len];
+        let trimmed_start = target.len() - target.trim_start().len();
+        let trimmed = target.trim();
+
+        if !trimmed.is_empty() {
+            let target_start = start + trimmed_start;
+            spans.push(LinkSpan {
+                target: target_start..target_start + trimmed.len(),
+            });
+        }
+        offset = start + end + 2;
+    }
+
+    spans
+}
+
+/// Link targets match titles case-insensitively and regardless of spacing
+fn link_key(target: &str) -> String {
+    target
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// `[[2026-03-14]]` links to a day rather than a note
+fn link_date(target: &str) -> Option<String> {
+    NaiveDate::parse_from_str(target.trim(), "%Y-%m-%d")
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// Decrypted titles of live notes by link key; the oldest note wins when
+/// several share a title
+async fn title_index(conn: &mut SqliteConnection) -> Result<HashMap<String, String>, AppError> {
+    let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT id, title
+        FROM notes
+        WHERE deleted_at IS NULL AND title IS NOT NULL
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut index = HashMap::new();
+    for (id, title) in rows {
+        if let Some(title) = crypto::decrypt_optional(&title)? {
+            let key = link_key(&title);
+            if !key.is_empty() {
+                index.insert(key, id);
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// A note's decrypted title as currently stored, read before it is
+/// overwritten so [`title_changed`] knows what to rewrite
+pub async fn stored_title(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+) -> Result<Option<String>, AppError> {
+    let title: Option<Option<String>> = sqlx::query_scalar("SELECT title FROM notes WHERE id = ?")
+        .bind(note_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    crypto::decrypt_optional(&title.flatten())
+}
+
+/// Re-parse a note's links from its (plaintext) content and store them
+pub async fn index_note(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    content: &str,
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM note_links WHERE source_id = ?")
+        .bind(note_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let mut targets: Vec<&str> = Vec::new();
+    for span in link_spans(content) {
+        let target = &content[span.target];
+        if !targets.iter().any(|t| link_key(t) == link_key(target)) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    // Only decrypt every title when there is a title link to resolve
+    let titles = if targets.iter().any(|t| link_date(t).is_none()) {
+        title_index(conn).await?
+    } else {
+        HashMap::new()
+    };
+
+    for (position, target) in targets.into_iter().enumerate() {
+        let target_date = link_date(target);
+        let target_note_id = match target_date {
+            Some(_) => None,
+            None => titles.get(&link_key(target)).cloned(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO note_links (source_id, position, target, target_note_id, target_date)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(note_id)
+        .bind(position as i64)
+        .bind(crypto::encrypt_text(target)?)
+        .bind(target_note_id)
+        .bind(target_date)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Replace the target of every link pointing at `old_key`, keeping any
+/// `#heading` or `|label` suffix
+fn rewrite_links(content: &str, old_key: &str, new_title: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut last = 0;
+
+    for span in link_spans(content) {
+        if link_key(&content[span.target.clone()]) == old_key {
+            out.push_str(&content[last..span.target.start]);
+            out.push_str(new_title);
+            last = span.target.end;
+        }
+    }
+
+    out.push_str(&content[last..]);
+    out
+}
+
+/// Keep links consistent after a note was created or its title changed.
+///
+/// Links to the old title are rewritten in every other note that uses them,
+/// and previously unresolved links to the new title now point at this note.
+/// When the note loses its title, or an older note already has the new one
+/// (the oldest note wins a shared title), nothing is rewritten: links to the
+/// note are resolved again by their own text, as [`rebuild`] would, so they
+/// pass to another note with the old title or become unresolved.
+/// Returns the ids of the notes whose content was rewritten.
+pub async fn title_changed(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    old_title: Option<&str>,
+    new_title: Option<&str>,
+) -> Result<Vec<String>, AppError> {
+    let old_key = old_title.map(link_key).filter(|k| !k.is_empty());
+    let new_title = new_title.map(str::trim).filter(|t| !t.is_empty());
+    let new_key = new_title.map(link_key);
+
+    if old_key == new_key {
+        return Ok(Vec::new());
+    }
+
+    // Called after the note itself was saved, so this reflects the new title
+    let titles = title_index(conn).await?;
+    let owns_new_title = new_key
+        .as_ref()
+        .is_some_and(|key| titles.get(key).map(String::as_str) == Some(note_id));
+
+    let mut rewritten = Vec::new();
+
+    if let Some(old_key) = &old_key {
+        match new_title.filter(|_| owns_new_title) {
+            Some(new_title) => {
+                let sources: Vec<String> = sqlx::query_scalar(
+                    r#"
+                    SELECT DISTINCT source_id
+                    FROM note_links
+                    WHERE target_note_id = ? AND source_id != ?
+                    "#,
+                )
+                .bind(note_id)
+                .bind(note_id)
+                .fetch_all(&mut *conn)
+                .await?;
+
+                for source_id in sources {
+                    rewrite_source(conn, &source_id, old_key, new_title).await?;
+                    rewritten.push(source_id);
+                }
+            }
+            None => resolve_again(conn, note_id, &titles).await?,
+        }
+    }
+
+    if let Some(new_key) = new_key.filter(|_| owns_new_title) {
+        let pending: Vec<(String, i64, String)> = sqlx::query_as(
+            r#"
+            SELECT source_id, position, target
+            FROM note_links
+            WHERE target_note_id IS NULL AND target_date IS NULL
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (source_id, position, target) in pending {
+            if link_key(&crypto::decrypt_text(&target)?) != new_key {
+                continue;
+            }
+            sqlx::query(
+                "UPDATE note_links SET target_note_id = ? WHERE source_id = ? AND position = ?",
+            )
+            .bind(note_id)
+            .bind(&source_id)
+            .bind(position)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(rewritten)
+}
+
+/// Resolve the links currently pointing at `note_id` by their own target
+/// text against `titles`
+async fn resolve_again(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    titles: &HashMap<String, String>,
+) -> Result<(), AppError> {
+    let links: Vec<(String, i64, String)> = sqlx::query_as(
+        "SELECT source_id, position, target FROM note_links WHERE target_note_id = ?",
+    )
+    .bind(note_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (source_id, position, target) in links {
+        let resolved = titles.get(&link_key(&crypto::decrypt_text(&target)?));
+        if resolved.map(String::as_str) == Some(note_id) {
+            continue;
+        }
+        sqlx::query(
+            "UPDATE note_links SET target_note_id = ? WHERE source_id = ? AND position = ?",
+        )
+        .bind(resolved)
+        .bind(&source_id)
+        .bind(position)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Point one note's links at a renamed note, keeping a revision of the
+/// content as it was
+async fn rewrite_source(
+    conn: &mut SqliteConnection,
+    source_id: &str,
+    old_key: &str,
+    new_title: &str,
+) -> Result<(), AppError> {
+    let (title, content): (Option<String>, String) =
+        sqlx::query_as("SELECT title, content FROM notes WHERE id = ?")
+            .bind(source_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let title = crypto::decrypt_optional(&title)?;
+    let content = crypto::decrypt_text(&content)?;
+    let updated = rewrite_links(&content, old_key, new_title);
+    if updated == content {
+        return Ok(());
+    }
+
+    crate::revisions::record_revision(conn, source_id, &title, &updated, true).await?;
+
+    sqlx::query("UPDATE notes SET content = ?, updated_at = ? WHERE id = ?")
+        .bind(crypto::encrypt_text(&updated)?)
+        .bind(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true))
+        .bind(source_id)
+        .execute(&mut *conn)
+        .await?;
+
+    index_note(conn, source_id, &updated).await
+}
+
+/// Re-scan every note for links, returning how many links were found
+pub async fn rebuild(pool: &SqlitePool) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let notes: Vec<(String, String)> = sqlx::query_as("SELECT id, content FROM notes")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for (id, content) in notes {
+        index_note(&mut tx, &id, &crypto::decrypt_text(&content)?).await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO metadata (key, value) VALUES (?, '1')
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value
+        "#,
+    )
+    .bind(LINKS_INDEXED_KEY)
+    .execute(&mut *tx)
+    .await?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM note_links")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(count as u64)
+}
+
+/// Index notes written before links were tracked. Needs the content to be
+/// readable, so an encrypted database is only indexed once unlocked.
+pub async fn rebuild_if_needed(pool: &SqlitePool) -> Result<(), AppError> {
+    let indexed: Option<String> = sqlx::query_scalar("SELECT value FROM metadata WHERE key = ?")
+        .bind(LINKS_INDEXED_KEY)
+        .fetch_optional(pool)
+        .await?;
+
+    if indexed.is_none() {
+        rebuild(pool).await?;
+    }
+    Ok(())
+}
+
+/// Links from a note to other notes and days, in the order they appear
+pub async fn outgoing_links(pool: &SqlitePool, note_id: &str) -> Result<Vec<NoteLink>, AppError> {
+    // Links into the trash count as unresolved
+    let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT l.target, t.id, l.target_date
+        FROM note_links l
+        LEFT JOIN notes t ON t.id = l.target_note_id AND t.deleted_at IS NULL
+        WHERE l.source_id = ?
+        ORDER BY l.position
+        "#,
+    )
+    .bind(note_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|(target, target_note_id, target_date)| {
+            Ok(NoteLink {
+                target: crypto::decrypt_text(&target)?,
+                target_note_id,
+                target_date,
+            })
+        })
+        .collect()
+}
+
+/// Notes linking to the given note, or to the given day with `[[YYYY-MM-DD]]`
+pub async fn backlinks(
+    pool: &SqlitePool,
+    note_id: Option<&str>,
+    date: Option<&str>,
+) -> Result<Vec<Note>, AppError> {
+    if note_id.is_none() && date.is_none() {
+        return Err(AppError::InvalidInput(
+            "Either a note id or a date is required".to_string(),
+        ));
+    }
+
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE deleted_at IS NULL
+          AND id IN (
+              SELECT source_id
+              FROM note_links
+              WHERE target_note_id = ? OR target_date = ?
+          )
+        ORDER BY date DESC, created_at DESC
+        "#,
+    )
+    .bind(note_id)
+    .bind(date)
+    .fetch_all(pool)
+    .await?;
+
+    crate::notes::hydrate(pool, &mut notes).await?;
+
+    Ok(notes)
+}
+
+/// Link targets no live note has as its title, grouped by target
+pub async fn unresolved_links(pool: &SqlitePool) -> Result<Vec<UnresolvedLink>, AppError> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT l.source_id, l.target
+        FROM note_links l
+        JOIN notes s ON s.id = l.source_id AND s.deleted_at IS NULL
+        LEFT JOIN notes t ON t.id = l.target_note_id AND t.deleted_at IS NULL
+        WHERE l.target_date IS NULL AND t.id IS NULL
+        ORDER BY s.date DESC, s.created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut unresolved: Vec<UnresolvedLink> = Vec::new();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+
+    for (source_id, target) in rows {
+        let target = crypto::decrypt_text(&target)?;
+        let index = *by_key.entry(link_key(&target)).or_insert_with(|| {
+            unresolved.push(UnresolvedLink {
+                target,
+                source_ids: Vec::new(),
+            });
+            unresolved.len() - 1
+        });
+        unresolved[index].source_ids.push(source_id);
+    }
+
+    unresolved.sort_by(|a, b| {
+        b.source_ids
+            .len()
+            .cmp(&a.source_ids.len())
+            .then_with(|| a.target.to_lowercase().cmp(&b.target.to_lowercase()))
+    });
+
+    Ok(unresolved)
+}
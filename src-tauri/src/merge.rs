@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::ops::Range;
+
+const CONFLICT_START: &str = "<<<<<<< yours\n";
+const CONFLICT_SEPARATOR: &str = "=======\n";
+const CONFLICT_END: &str = ">>>>>>> saved\n";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    /// The merged text. Regions both sides changed differently are kept
+    /// side by side between conflict markers.
+    pub content: String,
+    /// Whether the merge succeeded without any conflict markers
+    pub clean: bool,
+    pub conflicts: u32,
+}
+
+/// A changed region: lines `base` of the common ancestor became lines `other`
+struct Hunk {
+    base: Range<usize>,
+    other: Range<usize>,
+}
+
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+fn hunks(base: &[&str], other: &[&str]) -> Vec<Hunk> {
+    capture_diff_slices(Algorithm::Myers, base, other)
+        .iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+        .map(|(_, base, other)| Hunk { base, other })
+        .collect()
+}
+
+/// One side's version of the base lines `range`, given that side's hunks
+/// lying inside it
+fn side_text<'a>(
+    base: &[&'a str],
+    side: &[&'a str],
+    hunks: &[&Hunk],
+    range: Range<usize>,
+) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut cursor = range.start;
+    for hunk in hunks {
+        out.extend_from_slice(&base[cursor..hunk.base.start]);
+        out.extend_from_slice(&side[hunk.other.clone()]);
+        cursor = hunk.base.end;
+    }
+    out.extend_from_slice(&base[cursor..range.end]);
+    out
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    // Keep markers on their own line when the region ends the text
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Line-based three-way merge of two edits (`yours` and `saved`) of the
+/// same `base` text. Changes to different lines are combined; where both
+/// sides changed the same lines differently, both versions are kept
+/// between conflict markers for the user to resolve.
+pub fn merge(base: &str, yours: &str, saved: &str) -> MergeResult {
+    let base_lines = lines(base);
+    let your_lines = lines(yours);
+    let saved_lines = lines(saved);
+
+    // Tag each hunk with its side (0 = yours, 1 = saved) and walk them in
+    // base order, grouping those that overlap or touch
+    let your_hunks = hunks(&base_lines, &your_lines);
+    let saved_hunks = hunks(&base_lines, &saved_lines);
+    let mut all: Vec<(usize, &Hunk)> = your_hunks
+        .iter()
+        .map(|h| (0, h))
+        .chain(saved_hunks.iter().map(|h| (1, h)))
+        .collect();
+    all.sort_by_key(|(side, hunk)| (hunk.base.start, hunk.base.end, *side));
+
+    let mut content = String::new();
+    let mut conflicts = 0;
+    let mut cursor = 0;
+    let mut i = 0;
+
+    while i < all.len() {
+        let start = all[i].1.base.start;
+        let mut end = all[i].1.base.end;
+        let mut j = i + 1;
+        while j < all.len() && all[j].1.base.start <= end {
+            end = end.max(all[j].1.base.end);
+            j += 1;
+        }
+
+        let group = &all[i..j];
+        let mine: Vec<&Hunk> = group
+            .iter()
+            .filter(|(s, _)| *s == 0)
+            .map(|(_, h)| *h)
+            .collect();
+        let theirs: Vec<&Hunk> = group
+            .iter()
+            .filter(|(s, _)| *s == 1)
+            .map(|(_, h)| *h)
+            .collect();
+
+        content.extend(base_lines[cursor..start].iter().copied());
+
+        let your_text = side_text(&base_lines, &your_lines, &mine, start..end);
+        let saved_text = side_text(&base_lines, &saved_lines, &theirs, start..end);
+
+        if theirs.is_empty() || your_text == saved_text {
+            content.extend(your_text);
+        } else if mine.is_empty() {
+            content.extend(saved_text);
+        } else {
+            conflicts += 1;
+            push_lines(&mut content, &[]);
+            content.push_str(CONFLICT_START);
+            push_lines(&mut content, &your_text);
+            content.push_str(CONFLICT_SEPARATOR);
+            push_lines(&mut content, &saved_text);
+            content.push_str(CONFLICT_END);
+        }
+
+        cursor = end;
+        i = j;
+    }
+
+    content.extend(base_lines[cursor..].iter().copied());
+
+    MergeResult {
+        content,
+        clean: conflicts == 0,
+        conflicts,
+    }
+}
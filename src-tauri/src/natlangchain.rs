@@ -1,10 +1,12 @@
+use crate::error::AppError;
 use chrono::Utc;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use std::time::Duration;
 
 const API_TIMEOUT_SECS: u64 = 30;
+/// Service name used in error messages
+const SERVICE: &str = "NatLangChain";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -199,11 +201,11 @@ fn build_metadata(entry: &NatLangChainEntry) -> Option<serde_json::Value> {
 pub async fn validate_entry(
     api_url: &str,
     entry: &NatLangChainEntry,
-) -> Result<ValidationResult, String> {
+) -> Result<ValidationResult, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(API_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     let url = format!("{}/entry/validate", api_url.trim_end_matches('/'));
 
@@ -220,18 +222,18 @@ pub async fn validate_entry(
         .json(&request)
         .send()
         .await
-        .map_err(|e| format!("Validation request failed: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Validation API error ({}): {}", status, error_text));
+        return Err(AppError::response(SERVICE, status, &error_text));
     }
 
     let api_response: ApiValidationResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse validation response: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     // Parse NatLangChain response format
     let valid = api_response.overall_decision
@@ -276,11 +278,11 @@ pub async fn validate_entry(
 pub async fn publish_entry(
     api_url: &str,
     entry: &NatLangChainEntry,
-) -> Result<PublishResult, String> {
+) -> Result<PublishResult, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(API_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     let url = format!("{}/entry", api_url.trim_end_matches('/'));
 
@@ -297,7 +299,7 @@ pub async fn publish_entry(
         .json(&request)
         .send()
         .await
-        .map_err(|e| format!("Publish request failed: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     if !response.status().is_success() {
         let status = response.status();
@@ -314,7 +316,7 @@ pub async fn publish_entry(
     let api_response: ApiPublishResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse publish response: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     // NatLangChain returns status: "success" or "failure"
     let success = api_response.status
@@ -346,11 +348,11 @@ pub async fn publish_entry(
 pub async fn get_author_stats(
     api_url: &str,
     author_id: &str,
-) -> Result<ChainStats, String> {
+) -> Result<ChainStats, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(API_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     // Use the correct NatLangChain endpoint
     let url = format!(
@@ -363,7 +365,7 @@ pub async fn get_author_stats(
         .get(&url)
         .send()
         .await
-        .map_err(|e| format!("Stats request failed: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     if !response.status().is_success() {
         // Return empty stats if not found
@@ -378,7 +380,7 @@ pub async fn get_author_stats(
     let api_response: ApiAuthorEntriesResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse author entries response: {}", e))?;
+        .map_err(|e| AppError::request(SERVICE, e))?;
 
     // NatLangChain only provides entry count - other fields are placeholders
     Ok(ChainStats {
@@ -390,11 +392,11 @@ pub async fn get_author_stats(
 }
 
 /// Check if the NatLangChain API is available
-pub async fn check_connection(api_url: &str) -> Result<bool, String> {
+pub async fn check_connection(api_url: &str) -> Result<bool, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(5))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     let url = format!("{}/stats", api_url.trim_end_matches('/'));
 
@@ -405,6 +407,7 @@ pub async fn check_connection(api_url: &str) -> Result<bool, String> {
 }
 
 /// Create an entry with current timestamp
+#[allow(clippy::too_many_arguments)]
 pub fn create_entry(
     author: String,
     content: String,
@@ -0,0 +1,163 @@
+use crate::commands::Note;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use crate::weather::{JournalContext, WeatherData};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+
+/// Criteria for finding notes by the context they were written in.
+/// Every field is optional; the ones given are ANDed together.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextFilter {
+    /// Normalized weather condition, e.g. "rain" or "clear"
+    pub condition: Option<String>,
+    pub time_of_day: Option<String>,
+    pub day_of_week: Option<String>,
+    pub moon_phase: Option<String>,
+    pub min_temp_celsius: Option<f32>,
+    pub max_temp_celsius: Option<f32>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ContextRow {
+    note_id: String,
+    day_of_week: String,
+    time_of_day: String,
+    moon_phase: Option<String>,
+    weather_json: Option<String>,
+}
+
+impl ContextRow {
+    fn into_context(self) -> JournalContext {
+        JournalContext {
+            weather: self
+                .weather_json
+                .and_then(|json| serde_json::from_str::<WeatherData>(&json).ok()),
+            day_of_week: self.day_of_week,
+            time_of_day: self.time_of_day,
+            moon_phase: self.moon_phase,
+        }
+    }
+}
+
+/// Store (or replace) the context snapshot for a note.
+///
+/// The headline weather fields are kept in their own columns so they can be
+/// filtered on; the full `WeatherData` is kept as JSON for display.
+pub async fn save_context(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    context: &JournalContext,
+) -> Result<(), AppError> {
+    let weather_json = context
+        .weather
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO note_context (
+            note_id, day_of_week, time_of_day, moon_phase,
+            weather_condition, temp_celsius, location, weather_json, captured_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(note_id) DO UPDATE SET
+            day_of_week = excluded.day_of_week,
+            time_of_day = excluded.time_of_day,
+            moon_phase = excluded.moon_phase,
+            weather_condition = excluded.weather_condition,
+            temp_celsius = excluded.temp_celsius,
+            location = excluded.location,
+            weather_json = excluded.weather_json,
+            captured_at = excluded.captured_at
+        "#,
+    )
+    .bind(note_id)
+    .bind(&context.day_of_week)
+    .bind(&context.time_of_day)
+    .bind(&context.moon_phase)
+    .bind(context.weather.as_ref().map(|w| w.condition.as_str()))
+    .bind(context.weather.as_ref().map(|w| w.temp_celsius))
+    .bind(context.weather.as_ref().map(|w| w.location.as_str()))
+    .bind(weather_json)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fill in the `context` field of each note with a single query
+pub async fn load_contexts(pool: &SqlitePool, notes: &mut [Note]) -> Result<(), AppError> {
+    if notes.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+    let ids_json = serde_json::to_string(&ids)?;
+
+    let rows = sqlx::query_as::<_, ContextRow>(
+        r#"
+        SELECT note_id, day_of_week, time_of_day, moon_phase, weather_json
+        FROM note_context
+        WHERE note_id IN (SELECT value FROM json_each(?))
+        "#,
+    )
+    .bind(ids_json)
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_note: HashMap<String, JournalContext> = rows
+        .into_iter()
+        .map(|row| (row.note_id.clone(), row.into_context()))
+        .collect();
+
+    for note in notes.iter_mut() {
+        note.context = by_note.remove(&note.id);
+    }
+
+    Ok(())
+}
+
+/// Notes whose stored context matches the filter, newest first
+pub async fn notes_by_context(
+    pool: &SqlitePool,
+    filter: &ContextFilter,
+) -> Result<Vec<Note>, AppError> {
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT n.id, n.date, n.title, n.content, n.created_at, n.updated_at, n.deleted_at
+        FROM notes n
+        JOIN note_context c ON c.note_id = n.id
+        WHERE n.deleted_at IS NULL
+          AND (?1 IS NULL OR c.weather_condition = ?1 COLLATE NOCASE)
+          AND (?2 IS NULL OR c.time_of_day = ?2 COLLATE NOCASE)
+          AND (?3 IS NULL OR c.day_of_week = ?3 COLLATE NOCASE)
+          AND (?4 IS NULL OR c.moon_phase = ?4 COLLATE NOCASE)
+          AND (?5 IS NULL OR c.temp_celsius >= ?5)
+          AND (?6 IS NULL OR c.temp_celsius <= ?6)
+          AND (?7 IS NULL OR n.date >= ?7)
+          AND (?8 IS NULL OR n.date <= ?8)
+        ORDER BY n.date DESC, n.created_at DESC
+        "#,
+    )
+    .bind(&filter.condition)
+    .bind(&filter.time_of_day)
+    .bind(&filter.day_of_week)
+    .bind(&filter.moon_phase)
+    .bind(filter.min_temp_celsius)
+    .bind(filter.max_temp_celsius)
+    .bind(&filter.from_date)
+    .bind(&filter.to_date)
+    .fetch_all(pool)
+    .await?;
+
+    crate::notes::hydrate(pool, &mut notes).await?;
+
+    Ok(notes)
+}
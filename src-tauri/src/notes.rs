@@ -0,0 +1,187 @@
+use crate::commands::Note;
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use crate::note_context;
+use crate::stats;
+use crate::tags;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// Position after the last note of a page; pass it back to fetch the next one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteCursor {
+    pub date: String,
+    pub created_at: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePage {
+    pub notes: Vec<Note>,
+    /// `None` when there are no more notes in the range
+    pub next_cursor: Option<NoteCursor>,
+    /// True when `content` holds only a preview and must not be saved back
+    pub preview: bool,
+}
+
+/// Decrypt notes loaded from the `notes` table and attach their tags and journal context
+pub async fn hydrate(pool: &SqlitePool, notes: &mut [Note]) -> Result<(), AppError> {
+    crypto::decrypt_notes(notes)?;
+    tags::load_tags(pool, notes).await?;
+    note_context::load_contexts(pool, notes).await
+}
+
+/// A single note by id, including trashed ones
+pub async fn get_note(pool: &SqlitePool, id: &str) -> Result<Option<Note>, AppError> {
+    let note = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    let mut notes: Vec<Note> = note.into_iter().collect();
+    hydrate(pool, &mut notes).await?;
+    Ok(notes.pop())
+}
+
+/// Notes between `start` and `end` (inclusive, YYYY-MM-DD), oldest first.
+///
+/// Uses keyset pagination on (date, created_at, id) so every page is an index
+/// range scan regardless of how deep into the history it is.
+pub async fn notes_for_range(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+    cursor: Option<&NoteCursor>,
+    limit: Option<u32>,
+    preview_chars: Option<u32>,
+) -> Result<NotePage, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    // Ciphertext cannot be cut in SQL; encrypted previews are trimmed after decryption
+    let sql_preview_chars = if crypto::is_active() {
+        None
+    } else {
+        preview_chars
+    };
+
+    // Fetch one extra row to learn whether another page follows
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title,
+               CASE WHEN ?1 IS NULL THEN content ELSE substr(content, 1, ?1) END AS content,
+               created_at, updated_at, deleted_at
+        FROM notes
+        WHERE deleted_at IS NULL
+          AND date >= ?2 AND date <= ?3
+          AND (?4 IS NULL OR (date, created_at, id) > (?4, ?5, ?6))
+        ORDER BY date, created_at, id
+        LIMIT ?7
+        "#,
+    )
+    .bind(sql_preview_chars.map(|n| n as i64))
+    .bind(start)
+    .bind(end)
+    .bind(cursor.map(|c| c.date.as_str()))
+    .bind(cursor.map(|c| c.created_at.as_str()))
+    .bind(cursor.map(|c| c.id.as_str()))
+    .bind(limit as i64 + 1)
+    .fetch_all(pool)
+    .await?;
+
+    let next_cursor = if notes.len() > limit as usize {
+        notes.truncate(limit as usize);
+        notes.last().map(|n| NoteCursor {
+            date: n.date.clone(),
+            created_at: n.created_at.clone(),
+            id: n.id.clone(),
+        })
+    } else {
+        None
+    };
+
+    hydrate(pool, &mut notes).await?;
+
+    if let Some(chars) = preview_chars {
+        for note in notes.iter_mut() {
+            if let Some((end, _)) = note.content.char_indices().nth(chars as usize) {
+                note.content.truncate(end);
+            }
+        }
+    }
+
+    Ok(NotePage {
+        notes,
+        next_cursor,
+        preview: preview_chars.is_some(),
+    })
+}
+
+/// Activity for a single calendar day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaySummary {
+    pub date: String,
+    pub note_count: i64,
+    /// Words as counted by the statistics view
+    pub word_count: i64,
+    pub last_updated: String,
+}
+
+/// Per-day note counts for a whole year, or one month of it when `month` is given
+pub async fn calendar_summary(
+    pool: &SqlitePool,
+    year: i32,
+    month: Option<u32>,
+) -> Result<Vec<DaySummary>, AppError> {
+    let (start, end) = match month {
+        Some(m) if (1..=12).contains(&m) => (
+            format!("{:04}-{:02}-01", year, m),
+            format!("{:04}-{:02}-31", year, m),
+        ),
+        Some(m) => return Err(AppError::InvalidInput(format!("Invalid month: {}", m))),
+        None => (format!("{:04}-01-01", year), format!("{:04}-12-31", year)),
+    };
+
+    // Content is counted in Rust (decrypting when needed) so the calendar
+    // agrees with the statistics view on what a word is
+    let rows: Vec<(String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT date, content, updated_at
+        FROM notes
+        WHERE date >= ? AND date <= ? AND deleted_at IS NULL
+        "#,
+    )
+    .bind(&start)
+    .bind(&end)
+    .fetch_all(pool)
+    .await?;
+
+    let mut days: BTreeMap<String, DaySummary> = BTreeMap::new();
+    for (date, content, updated_at) in rows {
+        let words = stats::count_words(&crypto::decrypt_text(&content)?) as i64;
+        let day = days.entry(date.clone()).or_insert_with(|| DaySummary {
+            date,
+            note_count: 0,
+            word_count: 0,
+            last_updated: updated_at.clone(),
+        });
+        day.note_count += 1;
+        day.word_count += words;
+        if updated_at > day.last_updated {
+            day.last_updated = updated_at;
+        }
+    }
+
+    Ok(days.into_values().collect())
+}
@@ -1,12 +1,27 @@
 use crate::commands::{ChatMessage, OllamaStatus};
+use crate::error::AppError;
 use chrono::Utc;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
 use std::time::Duration;
+use tokio::sync::oneshot;
 
 // Timeout constants
 const STATUS_CHECK_TIMEOUT_SECS: u64 = 5;
 const CHAT_REQUEST_TIMEOUT_SECS: u64 = 120;
+/// A streamed reply may take arbitrarily long overall, so only connecting and
+/// the wait for each next chunk (including loading the model) are limited
+const STREAM_CONNECT_TIMEOUT_SECS: u64 = 10;
+const STREAM_IDLE_TIMEOUT_SECS: u64 = 120;
+/// Verifying a large download can go quiet for several minutes
+const PULL_IDLE_TIMEOUT_SECS: u64 = 600;
+const MODEL_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Service name used in error messages
+const SERVICE: &str = "Ollama";
 
 #[derive(Debug, Serialize)]
 struct OllamaChatRequest {
@@ -33,22 +48,211 @@ struct OllamaChatResponse {
     message: OllamaMessage,
 }
 
+/// One line of Ollama's NDJSON chat stream. The last line has `done` set and
+/// carries the generation stats.
+#[derive(Debug, Deserialize)]
+struct OllamaStreamChunk {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+    /// Durations are reported in nanoseconds
+    total_duration: Option<u64>,
+    eval_duration: Option<u64>,
+}
+
+/// Generation stats Ollama reports once a streamed reply is complete
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatStats {
+    pub prompt_eval_count: Option<u64>,
+    pub eval_count: Option<u64>,
+    pub total_duration_ms: Option<u64>,
+    pub eval_duration_ms: Option<u64>,
+}
+
+impl ChatStats {
+    fn from_chunk(chunk: &OllamaStreamChunk) -> Self {
+        let millis = |ns: Option<u64>| ns.map(|ns| ns / 1_000_000);
+        ChatStats {
+            prompt_eval_count: chunk.prompt_eval_count,
+            eval_count: chunk.eval_count,
+            total_duration_ms: millis(chunk.total_duration),
+            eval_duration_ms: millis(chunk.eval_duration),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaErrorResponse {
+    error: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct OllamaTagsResponse {
-    models: Vec<OllamaModel>,
+    models: Vec<ModelInfo>,
+}
+
+/// An installed model as listed by `/api/tags`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct ModelInfo {
+    pub name: String,
+    /// Size on disk in bytes
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub digest: String,
+    #[serde(default)]
+    pub modified_at: String,
+    #[serde(default)]
+    pub details: ModelDetails,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct ModelDetails {
+    pub format: Option<String>,
+    pub family: Option<String>,
+    pub families: Option<Vec<String>>,
+    /// e.g. "3.2B"
+    pub parameter_size: Option<String>,
+    /// e.g. "Q4_K_M"
+    pub quantization_level: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OllamaModel {
-    name: String,
+struct OllamaShowResponse {
+    license: Option<String>,
+    parameters: Option<String>,
+    template: Option<String>,
+    system: Option<String>,
+    #[serde(default)]
+    details: ModelDetails,
+    #[serde(default)]
+    model_info: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    capabilities: Vec<String>,
+    modified_at: Option<String>,
+}
+
+/// Everything `/api/show` tells about a model
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDescription {
+    pub name: String,
+    pub details: ModelDetails,
+    /// Maximum context window in tokens, when the model reports it
+    pub context_length: Option<u64>,
+    /// e.g. "completion", "vision", "tools"
+    pub capabilities: Vec<String>,
+    pub license: Option<String>,
+    /// Default generation parameters, one per line as in a Modelfile
+    pub parameters: Option<String>,
+    pub template: Option<String>,
+    pub system: Option<String>,
+    pub modified_at: Option<String>,
+}
+
+/// One step of a model download. `total` and `completed` are in bytes and
+/// only present while a layer is being downloaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct PullProgress {
+    pub status: String,
+    pub digest: Option<String>,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+}
+
+/// Chat requests in flight, by the id the frontend gave them, so they can be
+/// cancelled from another command
+#[derive(Default)]
+pub struct ChatRequests(Mutex<HashMap<String, oneshot::Sender<()>>>);
+
+impl ChatRequests {
+    /// Run a chat request, returning `None` if it was cancelled first.
+    ///
+    /// Cancelling drops the request, which closes the connection to Ollama and
+    /// makes it stop generating. Requests without an id cannot be cancelled.
+    pub async fn run<T>(
+        &self,
+        id: Option<String>,
+        request: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<Option<T>, AppError> {
+        let Some(id) = id else {
+            return request.await.map(Some);
+        };
+
+        let (cancel, cancelled) = oneshot::channel();
+        {
+            let mut requests = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            if requests.contains_key(&id) {
+                return Err(AppError::InvalidInput(format!(
+                    "A chat request with id {} is already running",
+                    id
+                )));
+            }
+            requests.insert(id.clone(), cancel);
+        }
+
+        let result = tokio::select! {
+            result = request => result.map(Some),
+            Ok(()) = cancelled => Ok(None),
+        };
+
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id);
+        result
+    }
+
+    /// Cancel a running request, returning whether one with that id was found
+    pub fn cancel(&self, id: &str) -> bool {
+        let cancel = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+        match cancel {
+            Some(cancel) => cancel.send(()).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// A connection failure means Ollama is not running, which the UI can say
+/// plainly instead of showing a transport error
+fn request_error(base_url: &str, e: reqwest::Error) -> AppError {
+    if e.is_connect() {
+        AppError::OllamaOffline(base_url.to_string())
+    } else {
+        AppError::request(SERVICE, e)
+    }
+}
+
+/// Ollama answers 404 when the requested model has not been pulled
+async fn response_error(model: &str, response: reqwest::Response) -> AppError {
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return AppError::ModelNotFound(model.to_string());
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<OllamaErrorResponse>(&body)
+        .map(|e| e.error)
+        .unwrap_or(body);
+    AppError::response(SERVICE, status, &message)
 }
 
 /// Check if Ollama is running and get available models
-pub async fn check_status(base_url: &str) -> Result<OllamaStatus, String> {
+pub async fn check_status(base_url: &str) -> Result<OllamaStatus, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(STATUS_CHECK_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
     let url = format!("{}/api/tags", base_url);
 
     match client.get(&url).send().await {
@@ -85,6 +289,30 @@ pub async fn check_status(base_url: &str) -> Result<OllamaStatus, String> {
     }
 }
 
+fn chat_request(
+    model: &str,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    temperature: f32,
+    max_tokens: u32,
+) -> OllamaChatRequest {
+    OllamaChatRequest {
+        model: model.to_string(),
+        messages: messages
+            .into_iter()
+            .map(|m| OllamaMessage {
+                role: m.role,
+                content: m.content,
+            })
+            .collect(),
+        stream,
+        options: OllamaOptions {
+            temperature,
+            num_predict: max_tokens,
+        },
+    }
+}
+
 /// Send a chat message to Ollama and get a response
 pub async fn send_message(
     base_url: &str,
@@ -92,46 +320,29 @@ pub async fn send_message(
     messages: Vec<ChatMessage>,
     temperature: f32,
     max_tokens: u32,
-) -> Result<ChatMessage, String> {
+) -> Result<ChatMessage, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(CHAT_REQUEST_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
     let url = format!("{}/api/chat", base_url);
-
-    let ollama_messages: Vec<OllamaMessage> = messages
-        .into_iter()
-        .map(|m| OllamaMessage {
-            role: m.role,
-            content: m.content,
-        })
-        .collect();
-
-    let request = OllamaChatRequest {
-        model: model.to_string(),
-        messages: ollama_messages,
-        stream: false,
-        options: OllamaOptions {
-            temperature,
-            num_predict: max_tokens,
-        },
-    };
+    let request = chat_request(model, messages, false, temperature, max_tokens);
 
     let response = client
         .post(&url)
         .json(&request)
         .send()
         .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+        .map_err(|e| request_error(base_url, e))?;
 
     if !response.status().is_success() {
-        return Err(format!("Ollama returned error: {}", response.status()));
+        return Err(response_error(model, response).await);
     }
 
     let chat_response: OllamaChatResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+        .map_err(|e| request_error(base_url, e))?;
 
     Ok(ChatMessage {
         role: chat_response.message.role,
@@ -139,3 +350,326 @@ pub async fn send_message(
         timestamp: Utc::now().to_rfc3339(),
     })
 }
+
+/// Client for streamed requests, which may take arbitrarily long overall
+fn stream_client() -> Result<Client, AppError> {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(STREAM_CONNECT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))
+}
+
+fn stream_timeout(idle_timeout: Duration) -> AppError {
+    AppError::Timeout(format!(
+        "Ollama sent nothing for {} seconds",
+        idle_timeout.as_secs()
+    ))
+}
+
+/// Send a request whose reply is streamed, giving up if no response starts
+/// within `idle_timeout`
+async fn send_streaming(
+    base_url: &str,
+    model: &str,
+    request: reqwest::RequestBuilder,
+    idle_timeout: Duration,
+) -> Result<reqwest::Response, AppError> {
+    let response = tokio::time::timeout(idle_timeout, request.send())
+        .await
+        .map_err(|_| stream_timeout(idle_timeout))?
+        .map_err(|e| request_error(base_url, e))?;
+
+    if !response.status().is_success() {
+        return Err(response_error(model, response).await);
+    }
+    Ok(response)
+}
+
+/// Read Ollama's NDJSON stream, passing each line to `on_line` until it
+/// reports the last one by returning true
+async fn read_stream<T: DeserializeOwned>(
+    base_url: &str,
+    mut response: reqwest::Response,
+    idle_timeout: Duration,
+    mut on_line: impl FnMut(T) -> Result<bool, AppError>,
+) -> Result<(), AppError> {
+    let mut pending: Vec<u8> = Vec::new();
+
+    loop {
+        let chunk = tokio::time::timeout(idle_timeout, response.chunk())
+            .await
+            .map_err(|_| stream_timeout(idle_timeout))?
+            .map_err(|e| request_error(base_url, e))?;
+
+        // A line may be split across network chunks, so only complete lines
+        // are parsed; whatever is left over when the body ends is the last one
+        let finished = chunk.is_none();
+        if let Some(bytes) = chunk {
+            pending.extend_from_slice(&bytes);
+        } else {
+            pending.push(b'\n');
+        }
+
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // A failure part way through arrives as an `error` line
+            if let Ok(error) = serde_json::from_str::<OllamaErrorResponse>(line.trim()) {
+                return Err(AppError::Service {
+                    message: format!("Ollama error: {}", error.error),
+                    retryable: false,
+                });
+            }
+
+            let parsed = serde_json::from_str(line.trim()).map_err(|e| AppError::Service {
+                message: format!("Ollama sent an unreadable response: {}", e),
+                retryable: false,
+            })?;
+            if on_line(parsed)? {
+                return Ok(());
+            }
+        }
+
+        if finished {
+            return Err(AppError::Network(
+                "Ollama closed the connection before finishing".to_string(),
+            ));
+        }
+    }
+}
+
+/// Send a chat message to Ollama and stream the reply, calling `on_chunk`
+/// with each piece of text as it is generated.
+///
+/// Returns the complete message and the stats from the final line of the
+/// stream.
+pub async fn stream_message(
+    base_url: &str,
+    model: &str,
+    messages: Vec<ChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    mut on_chunk: impl FnMut(&str),
+) -> Result<(ChatMessage, ChatStats), AppError> {
+    let url = format!("{}/api/chat", base_url);
+    let request = chat_request(model, messages, true, temperature, max_tokens);
+    let idle_timeout = Duration::from_secs(STREAM_IDLE_TIMEOUT_SECS);
+    let response = send_streaming(
+        base_url,
+        model,
+        stream_client()?.post(&url).json(&request),
+        idle_timeout,
+    )
+    .await?;
+
+    let mut role = "assistant".to_string();
+    let mut content = String::new();
+    let mut stats = ChatStats::default();
+
+    read_stream(
+        base_url,
+        response,
+        idle_timeout,
+        |chunk: OllamaStreamChunk| {
+            if let Some(message) = &chunk.message {
+                if !message.content.is_empty() {
+                    on_chunk(&message.content);
+                    content.push_str(&message.content);
+                }
+                role.clone_from(&message.role);
+            }
+            if chunk.done {
+                stats = ChatStats::from_chunk(&chunk);
+            }
+            Ok(chunk.done)
+        },
+    )
+    .await?;
+
+    let message = ChatMessage {
+        role,
+        content,
+        timestamp: Utc::now().to_rfc3339(),
+    };
+    Ok((message, stats))
+}
+
+fn model_client() -> Result<Client, AppError> {
+    Client::builder()
+        .timeout(Duration::from_secs(MODEL_REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// All installed models, by name
+pub async fn list_models(base_url: &str) -> Result<Vec<ModelInfo>, AppError> {
+    let url = format!("{}/api/tags", base_url);
+    let response = model_client()?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| request_error(base_url, e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::response(SERVICE, status, &body));
+    }
+
+    let mut models = response
+        .json::<OllamaTagsResponse>()
+        .await
+        .map_err(|e| request_error(base_url, e))?
+        .models;
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+/// Details of an installed model
+pub async fn show_model(base_url: &str, model: &str) -> Result<ModelDescription, AppError> {
+    let url = format!("{}/api/show", base_url);
+    let response = model_client()?
+        .post(&url)
+        .json(&serde_json::json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| request_error(base_url, e))?;
+
+    if !response.status().is_success() {
+        return Err(response_error(model, response).await);
+    }
+
+    let show: OllamaShowResponse = response
+        .json()
+        .await
+        .map_err(|e| request_error(base_url, e))?;
+
+    // Keys in `model_info` are prefixed with the architecture, e.g.
+    // "llama.context_length"
+    let context_length = show
+        .model_info
+        .get("general.architecture")
+        .and_then(|arch| arch.as_str())
+        .and_then(|arch| show.model_info.get(&format!("{}.context_length", arch)))
+        .and_then(|length| length.as_u64());
+
+    Ok(ModelDescription {
+        name: model.to_string(),
+        details: show.details,
+        context_length,
+        capabilities: show.capabilities,
+        license: show.license,
+        parameters: show.parameters,
+        template: show.template,
+        system: show.system,
+        modified_at: show.modified_at,
+    })
+}
+
+/// Download a model from the Ollama library, calling `on_progress` for each
+/// step Ollama reports. Pulling an installed model fetches any update.
+pub async fn pull_model(
+    base_url: &str,
+    model: &str,
+    mut on_progress: impl FnMut(PullProgress),
+) -> Result<(), AppError> {
+    let model = model.trim();
+    if model.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Model name cannot be empty".to_string(),
+        ));
+    }
+
+    let url = format!("{}/api/pull", base_url);
+    let idle_timeout = Duration::from_secs(PULL_IDLE_TIMEOUT_SECS);
+    let response = send_streaming(
+        base_url,
+        model,
+        stream_client()?
+            .post(&url)
+            .json(&serde_json::json!({ "model": model, "stream": true })),
+        idle_timeout,
+    )
+    .await?;
+
+    read_stream(
+        base_url,
+        response,
+        idle_timeout,
+        |progress: PullProgress| {
+            let done = progress.status == "success";
+            on_progress(progress);
+            Ok(done)
+        },
+    )
+    .await
+}
+
+/// Remove an installed model
+pub async fn delete_model(base_url: &str, model: &str) -> Result<(), AppError> {
+    let url = format!("{}/api/delete", base_url);
+    let response = model_client()?
+        .delete(&url)
+        .json(&serde_json::json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| request_error(base_url, e))?;
+
+    if !response.status().is_success() {
+        return Err(response_error(model, response).await);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// Embed each input with an embedding model, returning one vector per input.
+/// Inputs longer than the model's context are truncated by Ollama.
+pub async fn embed(
+    base_url: &str,
+    model: &str,
+    input: &[String],
+) -> Result<Vec<Vec<f32>>, AppError> {
+    // The first request may have to load the model
+    let client = Client::builder()
+        .timeout(Duration::from_secs(CHAT_REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
+    let url = format!("{}/api/embed", base_url);
+
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "model": model, "input": input, "truncate": true }))
+        .send()
+        .await
+        .map_err(|e| request_error(base_url, e))?;
+
+    if !response.status().is_success() {
+        return Err(response_error(model, response).await);
+    }
+
+    let embeddings = response
+        .json::<OllamaEmbedResponse>()
+        .await
+        .map_err(|e| request_error(base_url, e))?
+        .embeddings;
+
+    if embeddings.len() != input.len() {
+        return Err(AppError::Service {
+            message: format!(
+                "Ollama returned {} embeddings for {} inputs",
+                embeddings.len(),
+                input.len()
+            ),
+            retryable: false,
+        });
+    }
+    Ok(embeddings)
+}
@@ -0,0 +1,256 @@
+use crate::commands::Note;
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use sqlx::SqliteConnection;
+
+/// Edits closer together than this are folded into a single revision, so
+/// autosave does not produce one revision per keystroke pause.
+const COALESCE_WINDOW_MINUTES: i64 = 10;
+
+/// A snapshot of a note's title and content as it was before an update
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteRevision {
+    pub id: String,
+    pub note_id: String,
+    pub title: Option<String>,
+    pub content: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    /// One of "equal", "insert" or "delete"
+    pub kind: String,
+    pub content: String,
+    /// 1-based line number in the older text, if the line exists there
+    pub old_line: Option<usize>,
+    /// 1-based line number in the newer text, if the line exists there
+    pub new_line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionDiff {
+    pub old_title: Option<String>,
+    pub new_title: Option<String>,
+    pub lines: Vec<DiffLine>,
+}
+
+fn now_timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Snapshot the note's current state before it is overwritten.
+///
+/// Nothing is recorded when the note does not exist, when the incoming text is
+/// identical to what is stored, or (if `coalesce` is set) when the latest
+/// revision is recent enough that this edit belongs to the same burst.
+pub async fn record_revision(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    new_title: &Option<String>,
+    new_content: &str,
+    coalesce: bool,
+) -> Result<(), AppError> {
+    let current: Option<(Option<String>, String)> =
+        sqlx::query_as("SELECT title, content FROM notes WHERE id = ?")
+            .bind(note_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    let (title, content) = match current {
+        Some(row) => row,
+        None => return Ok(()),
+    };
+
+    // Stored text may be encrypted; compare what the user actually sees
+    if &crypto::decrypt_optional(&title)? == new_title
+        && crypto::decrypt_text(&content)? == new_content
+    {
+        return Ok(());
+    }
+
+    if coalesce {
+        let latest: Option<String> =
+            sqlx::query_scalar("SELECT MAX(created_at) FROM note_revisions WHERE note_id = ?")
+                .bind(note_id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+        let recent = latest
+            .and_then(|ts| DateTime::parse_from_rfc3339(&ts).ok())
+            .map(|ts| {
+                Utc::now() - ts.with_timezone(&Utc) < Duration::minutes(COALESCE_WINDOW_MINUTES)
+            })
+            .unwrap_or(false);
+
+        if recent {
+            return Ok(());
+        }
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO note_revisions (id, note_id, title, content, created_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(note_id)
+    .bind(title)
+    .bind(content)
+    .bind(now_timestamp())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// List all revisions of a note, newest first
+pub async fn list_revisions(
+    pool: &SqlitePool,
+    note_id: &str,
+) -> Result<Vec<NoteRevision>, AppError> {
+    let mut revisions = sqlx::query_as::<_, NoteRevision>(
+        r#"
+        SELECT id, note_id, title, content, created_at
+        FROM note_revisions
+        WHERE note_id = ?
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(note_id)
+    .fetch_all(pool)
+    .await?;
+
+    for revision in revisions.iter_mut() {
+        revision.title = crypto::decrypt_optional(&revision.title)?;
+        revision.content = crypto::decrypt_text(&revision.content)?;
+    }
+
+    Ok(revisions)
+}
+
+/// Load a revision's decrypted title and content, or the live note's when `revision_id` is `None`
+async fn load_text(
+    pool: &SqlitePool,
+    note_id: &str,
+    revision_id: Option<&str>,
+) -> Result<(Option<String>, String), AppError> {
+    let row: Option<(Option<String>, String)> = match revision_id {
+        Some(revision_id) => {
+            sqlx::query_as("SELECT title, content FROM note_revisions WHERE id = ? AND note_id = ?")
+                .bind(revision_id)
+                .bind(note_id)
+                .fetch_optional(pool)
+                .await
+        }
+        None => {
+            sqlx::query_as("SELECT title, content FROM notes WHERE id = ?")
+                .bind(note_id)
+                .fetch_optional(pool)
+                .await
+        }
+    }?;
+
+    let (title, content) = row.ok_or_else(|| match revision_id {
+        Some(id) => AppError::NotFound(format!("Revision not found: {}", id)),
+        None => AppError::NotFound(format!("Note not found: {}", note_id)),
+    })?;
+
+    Ok((
+        crypto::decrypt_optional(&title)?,
+        crypto::decrypt_text(&content)?,
+    ))
+}
+
+/// Line-level diff between two revisions; `None` on either side means the current note
+pub async fn diff_revisions(
+    pool: &SqlitePool,
+    note_id: &str,
+    from_revision_id: Option<&str>,
+    to_revision_id: Option<&str>,
+) -> Result<RevisionDiff, AppError> {
+    let (old_title, old_content) = load_text(pool, note_id, from_revision_id).await?;
+    let (new_title, new_content) = load_text(pool, note_id, to_revision_id).await?;
+
+    let diff = TextDiff::from_lines(&old_content, &new_content);
+    let lines = diff
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Insert => "insert",
+                ChangeTag::Delete => "delete",
+            }
+            .to_string(),
+            content: change.value().trim_end_matches('\n').to_string(),
+            old_line: change.old_index().map(|i| i + 1),
+            new_line: change.new_index().map(|i| i + 1),
+        })
+        .collect();
+
+    Ok(RevisionDiff {
+        old_title,
+        new_title,
+        lines,
+    })
+}
+
+/// Make an old revision the current content of its note.
+///
+/// The state being replaced is always snapshotted first, so a restore can
+/// itself be undone.
+pub async fn restore_revision(
+    pool: &SqlitePool,
+    note_id: &str,
+    revision_id: &str,
+) -> Result<Note, AppError> {
+    let (title, content) = load_text(pool, note_id, Some(revision_id)).await?;
+    let updated_at = now_timestamp();
+
+    let mut tx = pool.begin().await?;
+
+    record_revision(&mut tx, note_id, &title, &content, false).await?;
+    let previous_title = crate::links::stored_title(&mut tx, note_id).await?;
+
+    sqlx::query("UPDATE notes SET title = ?, content = ?, updated_at = ? WHERE id = ?")
+        .bind(crypto::encrypt_optional(&title)?)
+        .bind(crypto::encrypt_text(&content)?)
+        .bind(&updated_at)
+        .bind(note_id)
+        .execute(&mut *tx)
+        .await?;
+
+    crate::links::index_note(&mut tx, note_id, &content).await?;
+    crate::links::title_changed(
+        &mut tx,
+        note_id,
+        previous_title.as_deref(),
+        title.as_deref(),
+    )
+    .await?;
+
+    let mut note = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE id = ?
+        "#,
+    )
+    .bind(note_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    crate::notes::hydrate(pool, std::slice::from_mut(&mut note)).await?;
+
+    Ok(note)
+}
@@ -0,0 +1,321 @@
+use crate::commands::Note;
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+const SNIPPET_TOKENS: i64 = 16;
+
+/// Filters and pagination for a full-text search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOptions {
+    /// Inclusive lower bound on the note date (YYYY-MM-DD)
+    pub from_date: Option<String>,
+    /// Inclusive upper bound on the note date (YYYY-MM-DD)
+    pub to_date: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    #[sqlx(flatten)]
+    pub note: Note,
+    /// Title with matched terms wrapped in `<mark>` tags
+    pub title_highlight: Option<String>,
+    /// Excerpt of the content around the best match, with `<mark>` tags
+    pub snippet: String,
+    /// BM25 score; lower is a better match
+    pub rank: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub total: i64,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+/// Turn free-form user input into a safe FTS5 query.
+///
+/// Each whitespace-separated term is quoted so FTS5 operators and punctuation
+/// in the input cannot cause syntax errors, and gets a prefix wildcard so
+/// results appear while the user is still typing. Terms are implicitly ANDed.
+fn build_match_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| term.replace('"', "\"\""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Search note titles and content, best matches first
+pub async fn search_notes(
+    pool: &SqlitePool,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<SearchResults, AppError> {
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = options.offset.unwrap_or(0);
+
+    let match_query = match build_match_query(query) {
+        Some(q) => q,
+        None => {
+            return Ok(SearchResults {
+                hits: Vec::new(),
+                total: 0,
+                limit,
+                offset,
+            })
+        }
+    };
+
+    // Only ciphertext is indexed once encryption is enabled, so nothing in the
+    // index would match; scan the decrypted notes instead
+    if crypto::is_active() {
+        return search_decrypted(pool, query, options, limit, offset).await;
+    }
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM notes_fts
+        JOIN notes n ON n.rowid = notes_fts.rowid
+        WHERE notes_fts MATCH ?
+          AND n.deleted_at IS NULL
+          AND (? IS NULL OR n.date >= ?)
+          AND (? IS NULL OR n.date <= ?)
+        "#,
+    )
+    .bind(&match_query)
+    .bind(&options.from_date)
+    .bind(&options.from_date)
+    .bind(&options.to_date)
+    .bind(&options.to_date)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| AppError::Storage(format!("Search failed: {}", e)))?;
+
+    // Title matches weigh more than content matches when ranking
+    let hits = sqlx::query_as::<_, SearchHit>(
+        r#"
+        SELECT n.id, n.date, n.title, n.content, n.created_at, n.updated_at, n.deleted_at,
+               highlight(notes_fts, 0, '<mark>', '</mark>') AS title_highlight,
+               snippet(notes_fts, 1, '<mark>', '</mark>', '…', ?) AS snippet,
+               bm25(notes_fts, 10.0, 1.0) AS rank
+        FROM notes_fts
+        JOIN notes n ON n.rowid = notes_fts.rowid
+        WHERE notes_fts MATCH ?
+          AND n.deleted_at IS NULL
+          AND (? IS NULL OR n.date >= ?)
+          AND (? IS NULL OR n.date <= ?)
+        ORDER BY rank, n.date DESC
+        LIMIT ? OFFSET ?
+        "#,
+    )
+    .bind(SNIPPET_TOKENS)
+    .bind(&match_query)
+    .bind(&options.from_date)
+    .bind(&options.from_date)
+    .bind(&options.to_date)
+    .bind(&options.to_date)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| AppError::Storage(format!("Search failed: {}", e)))?;
+
+    Ok(SearchResults {
+        hits,
+        total,
+        limit,
+        offset,
+    })
+}
+
+/// Byte ranges of the words in `text`, split the way the search index
+/// tokenizes (runs of letters and digits)
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+fn lowercase_words(text: &str) -> Vec<String> {
+    word_spans(text)
+        .into_iter()
+        .map(|(start, end)| text[start..end].to_lowercase())
+        .collect()
+}
+
+/// Whether a word is matched by any term, each term acting as a prefix
+fn matches_any(word: &str, terms: &[String]) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.starts_with(term.as_str()))
+}
+
+/// Wrap every matching word in `<mark>` tags
+fn highlight(text: &str, terms: &[String]) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in word_spans(text) {
+        if matches_any(&text[start..end], terms) {
+            marked.push_str(&text[last..start]);
+            marked.push_str("<mark>");
+            marked.push_str(&text[start..end]);
+            marked.push_str("</mark>");
+            last = end;
+        }
+    }
+    marked.push_str(&text[last..]);
+    marked
+}
+
+/// About `SNIPPET_TOKENS` words of `text` around its first match, highlighted
+fn snippet(text: &str, terms: &[String]) -> String {
+    let spans = word_spans(text);
+    if spans.is_empty() {
+        return String::new();
+    }
+
+    let tokens = SNIPPET_TOKENS as usize;
+    let first = spans
+        .iter()
+        .position(|&(start, end)| matches_any(&text[start..end], terms))
+        .unwrap_or(0);
+    let start = first
+        .saturating_sub(tokens / 4)
+        .min(spans.len().saturating_sub(tokens));
+    let end = (start + tokens).min(spans.len());
+
+    let from = if start == 0 { 0 } else { spans[start].0 };
+    let to = if end == spans.len() {
+        text.len()
+    } else {
+        spans[end - 1].1
+    };
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        highlight(&text[from..to], terms),
+        if end < spans.len() { "…" } else { "" }
+    )
+}
+
+/// Search for an encrypted database by decrypting every note in range and
+/// scanning it. Matching and ranking follow the index: every term must begin
+/// a word of the title or content, and title matches weigh more. This reads
+/// the whole journal per query, so it is slower than the indexed search.
+async fn search_decrypted(
+    pool: &SqlitePool,
+    query: &str,
+    options: &SearchOptions,
+    limit: u32,
+    offset: u32,
+) -> Result<SearchResults, AppError> {
+    let terms = lowercase_words(query);
+    if terms.is_empty() {
+        return Ok(SearchResults {
+            hits: Vec::new(),
+            total: 0,
+            limit,
+            offset,
+        });
+    }
+
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE deleted_at IS NULL
+          AND (? IS NULL OR date >= ?)
+          AND (? IS NULL OR date <= ?)
+        "#,
+    )
+    .bind(&options.from_date)
+    .bind(&options.from_date)
+    .bind(&options.to_date)
+    .bind(&options.to_date)
+    .fetch_all(pool)
+    .await?;
+    crypto::decrypt_notes(&mut notes)?;
+
+    let mut hits: Vec<SearchHit> = notes
+        .into_iter()
+        .filter_map(|note| {
+            let title_words = lowercase_words(note.title.as_deref().unwrap_or(""));
+            let content_words = lowercase_words(&note.content);
+            let found = |term: &String| {
+                title_words
+                    .iter()
+                    .chain(&content_words)
+                    .any(|word| word.starts_with(term.as_str()))
+            };
+            if !terms.iter().all(found) {
+                return None;
+            }
+
+            let count = |words: &[String]| {
+                words
+                    .iter()
+                    .filter(|word| matches_any(word, &terms))
+                    .count() as f64
+            };
+            Some(SearchHit {
+                title_highlight: note.title.as_deref().map(|t| highlight(t, &terms)),
+                snippet: snippet(&note.content, &terms),
+                rank: -(10.0 * count(&title_words) + count(&content_words)),
+                note,
+            })
+        })
+        .collect();
+
+    hits.sort_by(|a, b| {
+        a.rank
+            .total_cmp(&b.rank)
+            .then_with(|| b.note.date.cmp(&a.note.date))
+    });
+    let total = hits.len() as i64;
+    let hits = hits
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+
+    Ok(SearchResults {
+        hits,
+        total,
+        limit,
+        offset,
+    })
+}
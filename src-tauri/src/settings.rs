@@ -0,0 +1,518 @@
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use chrono::NaiveTime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bump when a stored field is renamed or changes meaning, and teach
+/// `upgrade` how to convert the previous version.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Row in the `settings` table holding the serialized `Settings`
+const SETTINGS_KEY: &str = "app_settings";
+/// Key the frontend used inside the `tauri-plugin-store` file
+const STORE_SETTINGS_KEY: &str = "userSettings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateFormat {
+    #[serde(rename = "system")]
+    System,
+    #[serde(rename = "US")]
+    Us,
+    #[serde(rename = "EU")]
+    Eu,
+    #[serde(rename = "ISO")]
+    Iso,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeFormat {
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupFrequency {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl BackupFrequency {
+    pub fn hours(self) -> u32 {
+        match self {
+            BackupFrequency::Hourly => 1,
+            BackupFrequency::Daily => 24,
+            BackupFrequency::Weekly => 24 * 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Txt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonetizationModel {
+    Free,
+    Subscription,
+    PerEntry,
+    TipJar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryVisibility {
+    Public,
+    SubscribersOnly,
+    Private,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub theme: Theme,
+    pub start_minimized: bool,
+    pub start_on_login: bool,
+    pub minimize_on_close: bool,
+    pub always_on_top: bool,
+    pub show_in_taskbar: bool,
+    pub date_format: DateFormat,
+    pub time_format: TimeFormat,
+    pub spell_check: bool,
+    /// Milliseconds of inactivity before a note is saved
+    pub auto_save_delay: u32,
+    pub has_completed_onboarding: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::System,
+            start_minimized: false,
+            start_on_login: false,
+            minimize_on_close: true,
+            always_on_top: false,
+            show_in_taskbar: true,
+            date_format: DateFormat::System,
+            time_format: TimeFormat::TwelveHour,
+            spell_check: true,
+            auto_save_delay: 500,
+            has_completed_onboarding: false,
+        }
+    }
+}
+
+const DEFAULT_SYSTEM_PROMPT: &str =
+    "You are HeLpER, a friendly assistant embedded in a note-taking app.
+Your role is to help users:
+- Format and structure their notes
+- Expand brief notes into detailed content
+- Summarize long notes
+- Suggest improvements
+
+Keep responses concise and practical. Match the user's tone.
+When given a note for context, reference it naturally.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AiSettings {
+    pub ollama_url: String,
+    pub model: String,
+    pub temperature: f64,
+    pub max_tokens: u32,
+    pub system_prompt: String,
+    pub include_note_context: bool,
+    pub save_chat_history: bool,
+    /// Embed notes in the background so they can be found by meaning
+    pub semantic_search: bool,
+    /// Ollama model used for embeddings; changing it re-embeds every note
+    pub embedding_model: String,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Self {
+            ollama_url: "http://localhost:11434".to_string(),
+            model: "llama3.2:3b".to_string(),
+            temperature: 0.7,
+            max_tokens: 500,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            include_note_context: true,
+            save_chat_history: false,
+            semantic_search: false,
+            embedding_model: "nomic-embed-text".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DataSettings {
+    pub data_location: String,
+    pub backup_enabled: bool,
+    pub backup_frequency: BackupFrequency,
+    /// Number of most recent days to keep one backup for
+    pub backup_retention: u32,
+    /// Number of most recent weeks to keep one backup for
+    pub backup_weekly_retention: u32,
+    /// Absolute folder for backups; `None` uses `backups/` in the app data dir
+    pub backup_directory: Option<String>,
+    pub export_format: ExportFormat,
+    /// Days trashed notes are kept before being purged; 0 keeps them forever
+    pub trash_retention_days: u32,
+}
+
+impl Default for DataSettings {
+    fn default() -> Self {
+        Self {
+            data_location: String::new(),
+            backup_enabled: true,
+            backup_frequency: BackupFrequency::Daily,
+            backup_retention: 7,
+            backup_weekly_retention: 4,
+            backup_directory: None,
+            export_format: ExportFormat::Markdown,
+            trash_retention_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationSettings {
+    pub daily_reminder: bool,
+    /// Local time of the daily reminder, "HH:MM"
+    pub reminder_time: String,
+    pub sound: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            daily_reminder: false,
+            reminder_time: "20:00".to_string(),
+            sound: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WeatherSettings {
+    pub enabled: bool,
+    pub location: String,
+    pub auto_detect_location: bool,
+    pub temperature_unit: TemperatureUnit,
+    /// WeatherAPI.com key; empty disables weather lookups
+    pub api_key: String,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            location: String::new(),
+            auto_detect_location: true,
+            temperature_unit: TemperatureUnit::Celsius,
+            api_key: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NatLangChainSettings {
+    pub enabled: bool,
+    pub api_url: String,
+    pub author_id: String,
+    pub author_name: String,
+    pub default_monetization: MonetizationModel,
+    pub default_visibility: EntryVisibility,
+    pub default_price: f64,
+    pub include_weather_context: bool,
+    pub include_location_context: bool,
+    pub auto_audit_before_publish: bool,
+}
+
+impl Default for NatLangChainSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: "http://localhost:5000".to_string(),
+            author_id: String::new(),
+            author_name: String::new(),
+            default_monetization: MonetizationModel::Free,
+            default_visibility: EntryVisibility::Public,
+            default_price: 0.0,
+            include_weather_context: true,
+            include_location_context: false,
+            auto_audit_before_publish: true,
+        }
+    }
+}
+
+/// All user-facing configuration. Missing fields fall back to their defaults,
+/// so settings saved by an older build still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub version: u32,
+    pub app: AppSettings,
+    pub ai: AiSettings,
+    pub data: DataSettings,
+    pub notifications: NotificationSettings,
+    pub weather: WeatherSettings,
+    pub nat_lang_chain: NatLangChainSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            app: AppSettings::default(),
+            ai: AiSettings::default(),
+            data: DataSettings::default(),
+            notifications: NotificationSettings::default(),
+            weather: WeatherSettings::default(),
+            nat_lang_chain: NatLangChainSettings::default(),
+        }
+    }
+}
+
+trait Validate {
+    fn validate(&self) -> Result<(), AppError>;
+}
+
+fn validate_url(field: &str, value: &str) -> Result<(), AppError> {
+    let url = reqwest::Url::parse(value)
+        .map_err(|e| AppError::InvalidInput(format!("{} is not a valid URL: {}", field, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(AppError::InvalidInput(format!(
+            "{} must be an http(s) URL",
+            field
+        )));
+    }
+    Ok(())
+}
+
+impl Validate for AppSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        if !(100..=10_000).contains(&self.auto_save_delay) {
+            return Err(AppError::InvalidInput(
+                "Auto-save delay must be between 100 and 10000 ms".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for AiSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        validate_url("Ollama URL", &self.ollama_url)?;
+        if self.model.trim().is_empty() {
+            return Err(AppError::InvalidInput("Model name is required".to_string()));
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(AppError::InvalidInput(
+                "Temperature must be between 0 and 2".to_string(),
+            ));
+        }
+        if !(1..=32_768).contains(&self.max_tokens) {
+            return Err(AppError::InvalidInput(
+                "Max tokens must be between 1 and 32768".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for DataSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(dir) = &self.backup_directory {
+            if !Path::new(dir).is_absolute() {
+                return Err(AppError::InvalidInput(
+                    "Backup directory must be an absolute path".to_string(),
+                ));
+            }
+        }
+        if self.backup_retention == 0 && self.backup_weekly_retention == 0 {
+            return Err(AppError::InvalidInput(
+                "At least one daily or weekly backup must be kept".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for NotificationSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        NaiveTime::parse_from_str(&self.reminder_time, "%H:%M")
+            .map(|_| ())
+            .map_err(|_| {
+                AppError::InvalidInput("Reminder time must be in HH:MM format".to_string())
+            })
+    }
+}
+
+impl Validate for WeatherSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        let key = &self.api_key;
+        if !key.is_empty() && (key.len() > 64 || !key.chars().all(|c| c.is_ascii_alphanumeric())) {
+            return Err(AppError::InvalidInput(
+                "Weather API key must be up to 64 letters and digits".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for NatLangChainSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        validate_url("NatLangChain API URL", &self.api_url)?;
+        if !self.default_price.is_finite() || self.default_price < 0.0 {
+            return Err(AppError::InvalidInput(
+                "Default price cannot be negative".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for Settings {
+    fn validate(&self) -> Result<(), AppError> {
+        self.app.validate()?;
+        self.ai.validate()?;
+        self.data.validate()?;
+        self.notifications.validate()?;
+        self.weather.validate()?;
+        self.nat_lang_chain.validate()
+    }
+}
+
+/// Bring settings JSON written by an older build up to `SETTINGS_VERSION`
+fn upgrade(mut value: serde_json::Value) -> serde_json::Value {
+    // Version 1 is the first versioned layout; the store file had no version
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), SETTINGS_VERSION.into());
+    }
+    value
+}
+
+/// Take a section from untrusted JSON, falling back to defaults when it does
+/// not parse or validate, so one bad value cannot discard everything else.
+fn lenient_section<T: DeserializeOwned + Default + Validate>(
+    value: &serde_json::Value,
+    key: &str,
+) -> T {
+    value
+        .get(key)
+        .and_then(|section| serde_json::from_value::<T>(section.clone()).ok())
+        .filter(|section| section.validate().is_ok())
+        .unwrap_or_default()
+}
+
+fn lenient_settings(value: &serde_json::Value) -> Settings {
+    Settings {
+        version: SETTINGS_VERSION,
+        app: lenient_section(value, "app"),
+        ai: lenient_section(value, "ai"),
+        data: lenient_section(value, "data"),
+        notifications: lenient_section(value, "notifications"),
+        weather: lenient_section(value, "weather"),
+        nat_lang_chain: lenient_section(value, "natLangChain"),
+    }
+}
+
+async fn read_setting(pool: &SqlitePool, key: &str) -> Result<Option<String>, AppError> {
+    sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(AppError::from)
+}
+
+async fn write(pool: &SqlitePool, settings: &Settings) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(SETTINGS_KEY)
+    .bind(serde_json::to_string(settings)?)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Current settings, or the defaults if none have been saved yet
+pub async fn load(pool: &SqlitePool) -> Result<Settings, AppError> {
+    match read_setting(pool, SETTINGS_KEY).await? {
+        Some(json) => {
+            let value: serde_json::Value = serde_json::from_str(&json)
+                .map_err(|e| AppError::Storage(format!("Corrupt settings: {}", e)))?;
+            Ok(lenient_settings(&upgrade(value)))
+        }
+        None => Ok(Settings::default()),
+    }
+}
+
+/// Validate and persist a complete settings object
+pub async fn save(pool: &SqlitePool, mut settings: Settings) -> Result<Settings, AppError> {
+    settings.validate()?;
+    settings.version = SETTINGS_VERSION;
+    write(pool, &settings).await?;
+    Ok(settings)
+}
+
+/// One-time import of the settings the frontend kept in the
+/// `tauri-plugin-store` file. Does nothing once typed settings have been saved.
+pub async fn migrate_legacy(pool: &SqlitePool, store_path: &Path) -> Result<(), AppError> {
+    if read_setting(pool, SETTINGS_KEY).await?.is_some() {
+        return Ok(());
+    }
+
+    let store_settings = std::fs::read_to_string(store_path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|store| store.get(STORE_SETTINGS_KEY).cloned());
+    let settings = match &store_settings {
+        Some(value) => lenient_settings(value),
+        None => Settings::default(),
+    };
+
+    write(pool, &settings).await?;
+
+    if store_settings.is_some() {
+        println!("Migrated settings from {}", store_path.display());
+    }
+
+    Ok(())
+}
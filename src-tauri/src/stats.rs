@@ -0,0 +1,280 @@
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Notes read per query while computing statistics
+const STATS_PAGE_SIZE: i64 = 500;
+/// Indexed by `Weekday::num_days_from_monday`
+const WEEKDAY_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// The last computed statistics and the day they were computed on. Streaks
+/// depend on today's date, so a cached value from an earlier day is stale
+/// even when no note changed.
+static CACHE: RwLock<Option<(NaiveDate, WritingStats)>> = RwLock::new(None);
+/// Bumped by every `invalidate`, so statistics computed from data that
+/// changed while they were being computed are not cached
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodWords {
+    /// `YYYY-MM-DD`, ISO week `YYYY-Www`, or `YYYY-MM`
+    pub period: String,
+    pub notes: u32,
+    pub words: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Streak {
+    pub days: u32,
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WritingStats {
+    pub total_notes: u64,
+    pub total_words: u64,
+    pub average_words_per_entry: f64,
+    /// Averages over the whole period from the first entry until today
+    pub average_words_per_day: f64,
+    pub average_words_per_week: f64,
+    pub average_words_per_month: f64,
+    /// Only periods with at least one entry are listed, oldest first
+    pub words_per_day: Vec<PeriodWords>,
+    pub words_per_week: Vec<PeriodWords>,
+    pub words_per_month: Vec<PeriodWords>,
+    /// Consecutive days with an entry, ending today (or yesterday, so the
+    /// streak is not broken before today's entry is written)
+    pub current_streak: Streak,
+    pub longest_streak: Streak,
+    /// Entries by local hour of creation, 0-23
+    pub notes_by_hour: Vec<u32>,
+    /// Entries by weekday of the journal date, Monday first
+    pub notes_by_weekday: Vec<u32>,
+    pub most_active_hour: Option<u32>,
+    /// Lowercase English weekday name, e.g. "sunday"
+    pub most_active_weekday: Option<String>,
+}
+
+/// Words as a reader would count them; bare Markdown syntax such as `##`
+/// or `-` does not count
+pub(crate) fn count_words(text: &str) -> u64 {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count() as u64
+}
+
+/// Index of the largest count, or `None` when everything is zero
+fn busiest(counts: &[u32]) -> Option<usize> {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(index, _)| index)
+}
+
+fn streak(days: &[NaiveDate]) -> Streak {
+    Streak {
+        days: days.len() as u32,
+        start: days.first().map(|d| d.to_string()),
+        end: days.last().map(|d| d.to_string()),
+    }
+}
+
+/// Runs of consecutive days in a sorted list of days
+fn runs(days: &[NaiveDate]) -> Vec<&[NaiveDate]> {
+    days.chunk_by(|a, b| *b - *a == Duration::days(1)).collect()
+}
+
+/// Current and longest streaks; entries dated in the future (which the
+/// calendar allows) count towards the longest streak only
+fn streaks(days: &[NaiveDate], today: NaiveDate) -> (Streak, Streak) {
+    let past = &days[..days.partition_point(|day| *day <= today)];
+
+    let current = runs(past)
+        .pop()
+        .filter(|run| {
+            run.last()
+                .is_some_and(|last| today - *last <= Duration::days(1))
+        })
+        .unwrap_or(&[]);
+    // On a tie the most recent run wins
+    let longest = runs(days)
+        .into_iter()
+        .max_by_key(|run| run.len())
+        .unwrap_or(&[]);
+
+    (streak(current), streak(longest))
+}
+
+fn period_series(totals: BTreeMap<String, (u32, u64)>) -> Vec<PeriodWords> {
+    totals
+        .into_iter()
+        .map(|(period, (notes, words))| PeriodWords {
+            period,
+            notes,
+            words,
+        })
+        .collect()
+}
+
+async fn compute(pool: &SqlitePool, today: NaiveDate) -> Result<WritingStats, AppError> {
+    // Per-day totals are all that is needed afterwards, so content is
+    // counted page by page instead of loading every note at once
+    let mut per_day: BTreeMap<NaiveDate, (u32, u64)> = BTreeMap::new();
+    let mut notes_by_hour = vec![0u32; 24];
+    let mut notes_by_weekday = vec![0u32; 7];
+    let mut last_rowid = 0i64;
+
+    loop {
+        let page: Vec<(i64, String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT rowid, date, content, created_at
+            FROM notes
+            WHERE deleted_at IS NULL AND rowid > ?
+            ORDER BY rowid
+            LIMIT ?
+            "#,
+        )
+        .bind(last_rowid)
+        .bind(STATS_PAGE_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        let Some((rowid, ..)) = page.last() else {
+            break;
+        };
+        last_rowid = *rowid;
+
+        for (_, date, content, created_at) in page {
+            let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+                continue;
+            };
+            let words = count_words(&crypto::decrypt_text(&content)?);
+
+            let day = per_day.entry(date).or_default();
+            day.0 += 1;
+            day.1 += words;
+
+            notes_by_weekday[date.weekday().num_days_from_monday() as usize] += 1;
+            if let Ok(created_at) = DateTime::parse_from_rfc3339(&created_at) {
+                notes_by_hour[created_at.with_timezone(&Local).hour() as usize] += 1;
+            }
+        }
+    }
+
+    let mut per_week: BTreeMap<String, (u32, u64)> = BTreeMap::new();
+    let mut per_month: BTreeMap<String, (u32, u64)> = BTreeMap::new();
+    for (date, (notes, words)) in &per_day {
+        let week = date.iso_week();
+        let week = per_week
+            .entry(format!("{}-W{:02}", week.year(), week.week()))
+            .or_default();
+        week.0 += notes;
+        week.1 += words;
+
+        let month = per_month
+            .entry(date.format("%Y-%m").to_string())
+            .or_default();
+        month.0 += notes;
+        month.1 += words;
+    }
+
+    let total_notes: u64 = per_day.values().map(|(notes, _)| *notes as u64).sum();
+    let total_words: u64 = per_day.values().map(|(_, words)| *words).sum();
+
+    // Future-dated entries still count as at least a day of journaling
+    let span_days = per_day
+        .keys()
+        .next()
+        .map(|first| ((today - *first).num_days() + 1).max(1) as f64)
+        .unwrap_or(0.0);
+    let average = |period_days: f64| {
+        if span_days > 0.0 {
+            total_words as f64 / (span_days / period_days).max(1.0)
+        } else {
+            0.0
+        }
+    };
+
+    let days: Vec<NaiveDate> = per_day.keys().copied().collect();
+    let (current_streak, longest_streak) = streaks(&days, today);
+
+    Ok(WritingStats {
+        total_notes,
+        total_words,
+        average_words_per_entry: if total_notes > 0 {
+            total_words as f64 / total_notes as f64
+        } else {
+            0.0
+        },
+        average_words_per_day: average(1.0),
+        average_words_per_week: average(7.0),
+        average_words_per_month: average(365.25 / 12.0),
+        words_per_day: period_series(
+            per_day
+                .into_iter()
+                .map(|(date, totals)| (date.to_string(), totals))
+                .collect(),
+        ),
+        words_per_week: period_series(per_week),
+        words_per_month: period_series(per_month),
+        current_streak,
+        longest_streak,
+        most_active_hour: busiest(&notes_by_hour).map(|hour| hour as u32),
+        most_active_weekday: busiest(&notes_by_weekday).map(|day| WEEKDAY_NAMES[day].to_string()),
+        notes_by_hour,
+        notes_by_weekday,
+    })
+}
+
+/// Writing statistics, computed on first use and served from the cache
+/// until a note changes or the day rolls over
+pub async fn writing_stats(pool: &SqlitePool) -> Result<WritingStats, AppError> {
+    let today = Local::now().date_naive();
+
+    if let Ok(cache) = CACHE.read() {
+        if let Some((computed_on, stats)) = cache.as_ref() {
+            if *computed_on == today {
+                return Ok(stats.clone());
+            }
+        }
+    }
+
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let stats = compute(pool, today).await?;
+    if let Ok(mut cache) = CACHE.write() {
+        // `invalidate` bumps the generation under this lock, so it cannot
+        // slip in between the check and the store
+        if GENERATION.load(Ordering::SeqCst) == generation {
+            *cache = Some((today, stats.clone()));
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Drop the cached statistics; call after anything that changes notes
+pub fn invalidate() {
+    if let Ok(mut cache) = CACHE.write() {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        *cache = None;
+    }
+}
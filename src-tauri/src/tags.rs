@@ -0,0 +1,277 @@
+use crate::commands::Note;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub name: String,
+    /// Number of notes (excluding trashed ones) carrying the tag
+    pub count: i64,
+}
+
+/// Clean up a user-entered tag: trims whitespace and a leading `#`,
+/// collapses inner whitespace, and rejects empty results.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let name = tag
+        .trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn empty_tag_name() -> AppError {
+    AppError::InvalidInput("Tag name cannot be empty".to_string())
+}
+
+/// Normalize a list of tags, dropping empties and case-insensitive duplicates
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for name in tags.iter().filter_map(|t| normalize_tag(t)) {
+        if !result
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(&name))
+        {
+            result.push(name);
+        }
+    }
+    result
+}
+
+/// Look up a tag by name (case-insensitive), creating it if needed
+async fn ensure_tag(conn: &mut SqliteConnection, name: &str) -> Result<i64, AppError> {
+    sqlx::query("INSERT OR IGNORE INTO tags (name, created_at) VALUES (?, ?)")
+        .bind(name)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Drop tags that no longer label any note
+async fn prune_unused(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM note_tags)")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Replace the full set of tags on a note
+pub async fn set_note_tags(
+    conn: &mut SqliteConnection,
+    note_id: &str,
+    tags: &[String],
+) -> Result<Vec<String>, AppError> {
+    let tags = normalize_tags(tags);
+
+    sqlx::query("DELETE FROM note_tags WHERE note_id = ?")
+        .bind(note_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for name in &tags {
+        let tag_id = ensure_tag(conn, name).await?;
+        sqlx::query("INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?, ?)")
+            .bind(note_id)
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    prune_unused(conn).await?;
+
+    Ok(tags)
+}
+
+/// Fill in the `tags` field of each note with a single query
+pub async fn load_tags(pool: &SqlitePool, notes: &mut [Note]) -> Result<(), AppError> {
+    if notes.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+    let ids_json = serde_json::to_string(&ids)?;
+
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT nt.note_id, t.name
+        FROM note_tags nt
+        JOIN tags t ON t.id = nt.tag_id
+        WHERE nt.note_id IN (SELECT value FROM json_each(?))
+        ORDER BY t.name COLLATE NOCASE
+        "#,
+    )
+    .bind(ids_json)
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_note: HashMap<String, Vec<String>> = HashMap::new();
+    for (note_id, name) in rows {
+        by_note.entry(note_id).or_default().push(name);
+    }
+
+    for note in notes.iter_mut() {
+        note.tags = Some(by_note.remove(&note.id).unwrap_or_default());
+    }
+
+    Ok(())
+}
+
+/// All tags with the number of live notes using each, for the sidebar
+pub async fn list_tags(pool: &SqlitePool) -> Result<Vec<TagCount>, AppError> {
+    sqlx::query_as::<_, TagCount>(
+        r#"
+        SELECT t.name, COUNT(n.id) AS count
+        FROM tags t
+        LEFT JOIN note_tags nt ON nt.tag_id = t.id
+        LEFT JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+        GROUP BY t.id
+        ORDER BY count DESC, t.name COLLATE NOCASE
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Fold every source tag into `target` across all notes, then remove the sources
+pub async fn merge_tags(
+    pool: &SqlitePool,
+    sources: &[String],
+    target: &str,
+) -> Result<(), AppError> {
+    let target = normalize_tag(target).ok_or_else(empty_tag_name)?;
+    let mut tx = pool.begin().await?;
+
+    let target_id = ensure_tag(&mut tx, &target).await?;
+
+    for source in normalize_tags(sources) {
+        let source_id: Option<i64> = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+            .bind(&source)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let source_id = match source_id {
+            Some(id) if id == target_id => continue,
+            Some(id) => id,
+            None => return Err(AppError::NotFound(format!("Tag not found: {}", source))),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO note_tags (note_id, tag_id)
+            SELECT note_id, ? FROM note_tags WHERE tag_id = ?
+            "#,
+        )
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    prune_unused(&mut tx).await?;
+    tx.commit().await.map_err(AppError::from)
+}
+
+/// Rename a tag everywhere; renaming onto an existing tag merges the two
+pub async fn rename_tag(pool: &SqlitePool, from: &str, to: &str) -> Result<(), AppError> {
+    let from = normalize_tag(from).ok_or_else(empty_tag_name)?;
+    let to = normalize_tag(to).ok_or_else(empty_tag_name)?;
+
+    let target_exists: Option<i64> = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(&to)
+        .fetch_optional(pool)
+        .await?;
+
+    // A case-only change ("work" -> "Work") is a plain rename, not a merge
+    if target_exists.is_some() && !from.eq_ignore_ascii_case(&to) {
+        return merge_tags(pool, &[from], &to).await;
+    }
+
+    let renamed = sqlx::query("UPDATE tags SET name = ? WHERE name = ?")
+        .bind(&to)
+        .bind(&from)
+        .execute(pool)
+        .await?;
+
+    if renamed.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Tag not found: {}", from)));
+    }
+
+    Ok(())
+}
+
+/// Remove a tag from all notes
+pub async fn delete_tag(pool: &SqlitePool, name: &str) -> Result<(), AppError> {
+    let name = normalize_tag(name).ok_or_else(empty_tag_name)?;
+
+    let deleted = sqlx::query("DELETE FROM tags WHERE name = ?")
+        .bind(&name)
+        .execute(pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Tag not found: {}", name)));
+    }
+    Ok(())
+}
+
+/// Notes carrying the given tags: all of them when `match_all`, otherwise any
+pub async fn notes_by_tag(
+    pool: &SqlitePool,
+    tags: &[String],
+    match_all: bool,
+) -> Result<Vec<Note>, AppError> {
+    let tags = normalize_tags(tags);
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let required = if match_all { tags.len() as i64 } else { 1 };
+    let tags_json = serde_json::to_string(&tags)?;
+
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE deleted_at IS NULL
+          AND id IN (
+              SELECT nt.note_id
+              FROM note_tags nt
+              JOIN tags t ON t.id = nt.tag_id
+              WHERE t.name IN (SELECT value FROM json_each(?))
+              GROUP BY nt.note_id
+              HAVING COUNT(DISTINCT t.id) >= ?
+          )
+        ORDER BY date DESC, created_at DESC
+        "#,
+    )
+    .bind(tags_json)
+    .bind(required)
+    .fetch_all(pool)
+    .await?;
+
+    crate::notes::hydrate(pool, &mut notes).await?;
+
+    Ok(notes)
+}
@@ -0,0 +1,355 @@
+use crate::crypto;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+use crate::settings::{self, TemperatureUnit};
+use crate::weather::{self, JournalContext};
+use chrono::{Datelike, NaiveDate, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+
+/// Indexed by `Weekday::num_days_from_monday`, matching `template_defaults.weekday`
+const WEEKDAY_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    /// Markdown with `{{placeholder}}`s, see `render`
+    pub content: String,
+    /// Lowercase weekday names, e.g. "monday", on which new notes start
+    /// from this template
+    #[serde(default)]
+    pub default_weekdays: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TemplateRow {
+    id: String,
+    name: String,
+    content: String,
+    created_at: String,
+    updated_at: String,
+}
+
+fn weekday_index(name: &str) -> Result<i64, AppError> {
+    WEEKDAY_NAMES
+        .iter()
+        .position(|day| day.eq_ignore_ascii_case(name.trim()))
+        .map(|index| index as i64)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown weekday: {}", name)))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| AppError::InvalidInput(format!("Invalid date: {}", date)))
+}
+
+fn validate(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Template name cannot be empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Make the template the default for exactly the given weekdays, taking
+/// them over from any other template
+async fn set_default_weekdays(
+    conn: &mut SqliteConnection,
+    template_id: &str,
+    weekdays: &[String],
+) -> Result<Vec<String>, AppError> {
+    let mut indexes = weekdays
+        .iter()
+        .map(|day| weekday_index(day))
+        .collect::<Result<Vec<_>, _>>()?;
+    indexes.sort_unstable();
+    indexes.dedup();
+
+    sqlx::query("DELETE FROM template_defaults WHERE template_id = ?")
+        .bind(template_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for weekday in &indexes {
+        sqlx::query(
+            r#"
+            INSERT INTO template_defaults (weekday, template_id) VALUES (?, ?)
+            ON CONFLICT(weekday) DO UPDATE SET template_id = excluded.template_id
+            "#,
+        )
+        .bind(weekday)
+        .bind(template_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(indexes
+        .into_iter()
+        .map(|index| WEEKDAY_NAMES[index as usize].to_string())
+        .collect())
+}
+
+/// All templates by name
+pub async fn list_templates(pool: &SqlitePool) -> Result<Vec<Template>, AppError> {
+    let rows = sqlx::query_as::<_, TemplateRow>(
+        "SELECT id, name, content, created_at, updated_at FROM templates",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let defaults: Vec<(i64, String)> =
+        sqlx::query_as("SELECT weekday, template_id FROM template_defaults ORDER BY weekday")
+            .fetch_all(pool)
+            .await?;
+    let mut weekdays: HashMap<String, Vec<String>> = HashMap::new();
+    for (weekday, template_id) in defaults {
+        if let Some(name) = WEEKDAY_NAMES.get(weekday as usize) {
+            weekdays
+                .entry(template_id)
+                .or_default()
+                .push(name.to_string());
+        }
+    }
+
+    // Names may be encrypted, so they are sorted after decrypting
+    let mut templates = rows
+        .into_iter()
+        .map(|row| {
+            Ok(Template {
+                default_weekdays: weekdays.remove(&row.id).unwrap_or_default(),
+                name: crypto::decrypt_text(&row.name)?,
+                content: crypto::decrypt_text(&row.content)?,
+                id: row.id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    templates.sort_by_key(|template| template.name.to_lowercase());
+
+    Ok(templates)
+}
+
+async fn get_template(pool: &SqlitePool, id: &str) -> Result<Template, AppError> {
+    list_templates(pool)
+        .await?
+        .into_iter()
+        .find(|template| template.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("Template not found: {}", id)))
+}
+
+pub async fn create_template(
+    pool: &SqlitePool,
+    name: &str,
+    content: &str,
+    default_weekdays: &[String],
+) -> Result<Template, AppError> {
+    let name = validate(name)?;
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let id = uuid::Uuid::new_v4().to_string();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO templates (id, name, content, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&id)
+    .bind(crypto::encrypt_text(&name)?)
+    .bind(crypto::encrypt_text(content)?)
+    .bind(&now)
+    .bind(&now)
+    .execute(&mut *tx)
+    .await?;
+    let default_weekdays = set_default_weekdays(&mut tx, &id, default_weekdays).await?;
+    tx.commit().await?;
+
+    Ok(Template {
+        id,
+        name,
+        content: content.to_string(),
+        default_weekdays,
+        created_at: now.clone(),
+        updated_at: now,
+    })
+}
+
+/// Save a template's name, content and default weekdays
+pub async fn update_template(pool: &SqlitePool, template: &Template) -> Result<Template, AppError> {
+    let name = validate(&template.name)?;
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    let mut tx = pool.begin().await?;
+    let updated =
+        sqlx::query("UPDATE templates SET name = ?, content = ?, updated_at = ? WHERE id = ?")
+            .bind(crypto::encrypt_text(&name)?)
+            .bind(crypto::encrypt_text(&template.content)?)
+            .bind(&now)
+            .bind(&template.id)
+            .execute(&mut *tx)
+            .await?;
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "Template not found: {}",
+            template.id
+        )));
+    }
+    let default_weekdays =
+        set_default_weekdays(&mut tx, &template.id, &template.default_weekdays).await?;
+    tx.commit().await?;
+
+    Ok(Template {
+        name,
+        default_weekdays,
+        updated_at: now,
+        ..template.clone()
+    })
+}
+
+pub async fn delete_template(pool: &SqlitePool, id: &str) -> Result<(), AppError> {
+    let deleted = sqlx::query("DELETE FROM templates WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Template not found: {}", id)));
+    }
+
+    Ok(())
+}
+
+fn format_weather(context: &JournalContext, unit: TemperatureUnit) -> String {
+    match &context.weather {
+        Some(weather) => {
+            let (temp, symbol) = match unit {
+                TemperatureUnit::Celsius => (weather.temp_celsius, "C"),
+                TemperatureUnit::Fahrenheit => (weather.temp_fahrenheit, "F"),
+            };
+            format!("{}, {:.0}°{}", weather.condition_text, temp, symbol)
+        }
+        None => String::new(),
+    }
+}
+
+/// Fill in the placeholders of a template for a note on `date`.
+///
+/// `{{date}}` and `{{weekday}}` describe the note's day; `{{weather}}`,
+/// `{{moon_phase}}` and `{{time_of_day}}` come from the journal context and
+/// are left empty when it has no value for them. Unknown placeholders are
+/// kept as written.
+pub fn render(
+    content: &str,
+    date: NaiveDate,
+    context: &JournalContext,
+    unit: TemperatureUnit,
+) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start + 2..start + 2 + len];
+        let value = match placeholder.trim() {
+            "date" => Some(date.format("%Y-%m-%d").to_string()),
+            "weekday" => Some(date.format("%A").to_string()),
+            "weather" => Some(format_weather(context, unit)),
+            "moon_phase" => Some(context.moon_phase.clone().unwrap_or_default()),
+            "time_of_day" => Some(context.time_of_day.clone()),
+            _ => None,
+        };
+
+        out.push_str(&rest[..start]);
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + len + 4]),
+        }
+        rest = &rest[start + len + 4..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Render a template for a note on `date`, looking up the journal context
+/// from the weather settings when the caller has none
+pub async fn apply_template(
+    pool: &SqlitePool,
+    id: &str,
+    date: &str,
+    context: Option<JournalContext>,
+) -> Result<String, AppError> {
+    let date = parse_date(date)?;
+    let template = get_template(pool, id).await?;
+    let config = settings::load(pool).await?.weather;
+
+    let context = match context {
+        Some(context) => context,
+        None if config.enabled => {
+            weather::get_journal_context(&config.api_key, &config.location).await?
+        }
+        None => weather::local_context(),
+    };
+
+    Ok(render(
+        &template.content,
+        date,
+        &context,
+        config.temperature_unit,
+    ))
+}
+
+/// The default template for the weekday of `date`, rendered, if one is set
+pub async fn render_default(
+    pool: &SqlitePool,
+    date: &str,
+    context: Option<&JournalContext>,
+) -> Result<Option<String>, AppError> {
+    let date = parse_date(date)?;
+    let weekday = date.weekday().num_days_from_monday() as i64;
+
+    let content: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT t.content
+        FROM template_defaults d
+        JOIN templates t ON t.id = d.template_id
+        WHERE d.weekday = ?
+        "#,
+    )
+    .bind(weekday)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(content) = content else {
+        return Ok(None);
+    };
+
+    // Creating a note never waits on a weather lookup; the note's own
+    // context is used when the caller captured one
+    let unit = settings::load(pool).await?.weather.temperature_unit;
+    let local = weather::local_context();
+    Ok(Some(render(
+        &crypto::decrypt_text(&content)?,
+        date,
+        context.unwrap_or(&local),
+        unit,
+    )))
+}
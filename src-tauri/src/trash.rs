@@ -0,0 +1,92 @@
+use crate::commands::Note;
+use crate::database::SqlitePool;
+use crate::error::AppError;
+
+/// List soft-deleted notes, most recently deleted first
+pub async fn list_trashed(pool: &SqlitePool) -> Result<Vec<Note>, AppError> {
+    let mut notes = sqlx::query_as::<_, Note>(
+        r#"
+        SELECT id, date, title, content, created_at, updated_at, deleted_at
+        FROM notes
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    crate::notes::hydrate(pool, &mut notes).await?;
+
+    Ok(notes)
+}
+
+/// Move notes back out of the trash, returning how many were restored
+pub async fn restore(pool: &SqlitePool, ids: &[String]) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut restored = 0;
+
+    for id in ids {
+        restored += sqlx::query(
+            "UPDATE notes SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+
+    tx.commit().await?;
+
+    Ok(restored)
+}
+
+/// Permanently delete trashed notes, returning how many were removed.
+///
+/// Only notes already in the trash can be purged, so a stale id from the UI
+/// can never destroy a live note.
+pub async fn purge(pool: &SqlitePool, ids: &[String]) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut purged = 0;
+
+    for id in ids {
+        purged += sqlx::query("DELETE FROM notes WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+
+    tx.commit().await?;
+
+    Ok(purged)
+}
+
+/// Permanently delete everything in the trash
+pub async fn empty(pool: &SqlitePool) -> Result<u64, AppError> {
+    sqlx::query("DELETE FROM notes WHERE deleted_at IS NOT NULL")
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected())
+        .map_err(AppError::from)
+}
+
+/// Permanently delete notes that have been in the trash longer than the retention period
+pub async fn purge_expired(pool: &SqlitePool) -> Result<u64, AppError> {
+    let days = crate::settings::load(pool).await?.data.trash_retention_days;
+    if days == 0 {
+        return Ok(0);
+    }
+
+    sqlx::query(
+        r#"
+        DELETE FROM notes
+        WHERE deleted_at IS NOT NULL
+          AND julianday(deleted_at) < julianday('now', ?)
+        "#,
+    )
+    .bind(format!("-{} days", days))
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+    .map_err(AppError::from)
+}
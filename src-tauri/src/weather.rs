@@ -1,3 +1,4 @@
+use crate::error::AppError;
 use chrono::{Local, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,9 @@ use urlencoding::encode;
 
 const WEATHER_API_TIMEOUT_SECS: u64 = 10;
 const WEATHERAPI_BASE_URL: &str = "https://api.weatherapi.com/v1";
+/// Service names used in error messages
+const WEATHER_SERVICE: &str = "the weather service";
+const LOCATION_SERVICE: &str = "the location service";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -132,15 +136,15 @@ fn get_moon_phase() -> String {
 use chrono::Timelike;
 
 /// Fetch weather data from WeatherAPI.com
-pub async fn fetch_weather(api_key: &str, location: &str) -> Result<WeatherData, String> {
+pub async fn fetch_weather(api_key: &str, location: &str) -> Result<WeatherData, AppError> {
     if api_key.is_empty() {
-        return Err("Weather API key not configured".to_string());
+        return Err(AppError::Unauthorized("Weather API key not configured".to_string()));
     }
 
     let client = Client::builder()
         .timeout(Duration::from_secs(WEATHER_API_TIMEOUT_SECS))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     let url = format!(
         "{}/current.json?key={}&q={}&aqi=no",
@@ -151,18 +155,18 @@ pub async fn fetch_weather(api_key: &str, location: &str) -> Result<WeatherData,
         .get(&url)
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch weather: {}", e))?;
+        .map_err(|e| AppError::request(WEATHER_SERVICE, e))?;
 
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Weather API error ({}): {}", status, error_text));
+        return Err(AppError::response(WEATHER_SERVICE, status, &error_text));
     }
 
     let api_response: WeatherApiResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse weather response: {}", e))?;
+        .map_err(|e| AppError::request(WEATHER_SERVICE, e))?;
 
     let location_str = format!(
         "{}, {}",
@@ -190,40 +194,45 @@ pub async fn fetch_weather(api_key: &str, location: &str) -> Result<WeatherData,
 }
 
 /// Auto-detect location from IP address
-pub async fn detect_location() -> Result<String, String> {
+pub async fn detect_location() -> Result<String, AppError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(5))
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
     let response = client
         .get("https://ip-api.com/json/")
         .send()
         .await
-        .map_err(|e| format!("Failed to detect location: {}", e))?;
+        .map_err(|e| AppError::request(LOCATION_SERVICE, e))?;
 
     if !response.status().is_success() {
-        return Err("Failed to detect location from IP".to_string());
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(AppError::response(LOCATION_SERVICE, status, &error_text));
     }
 
     let ip_data: IpApiResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse location response: {}", e))?;
+        .map_err(|e| AppError::request(LOCATION_SERVICE, e))?;
 
     if ip_data.status != "success" {
-        return Err("Location detection failed".to_string());
+        return Err(AppError::Service {
+            message: "Location detection failed".to_string(),
+            retryable: false,
+        });
     }
 
     match (ip_data.city, ip_data.country) {
         (Some(city), Some(country)) => Ok(format!("{}, {}", city, country)),
         (Some(city), None) => Ok(city),
-        _ => Err("Could not determine location".to_string()),
+        _ => Err(AppError::NotFound("Could not determine location".to_string())),
     }
 }
 
 /// Get full journal context including weather and time information
-pub async fn get_journal_context(api_key: &str, location: &str) -> Result<JournalContext, String> {
+pub async fn get_journal_context(api_key: &str, location: &str) -> Result<JournalContext, AppError> {
     let weather = if !api_key.is_empty() && !location.is_empty() {
         match fetch_weather(api_key, location).await {
             Ok(w) => Some(w),
@@ -238,8 +247,16 @@ pub async fn get_journal_context(api_key: &str, location: &str) -> Result<Journa
 
     Ok(JournalContext {
         weather,
+        ..local_context()
+    })
+}
+
+/// Journal context from the clock alone, without looking up the weather
+pub fn local_context() -> JournalContext {
+    JournalContext {
+        weather: None,
         day_of_week: get_day_of_week(),
         time_of_day: get_time_of_day(),
         moon_phase: Some(get_moon_phase()),
-    })
+    }
 }
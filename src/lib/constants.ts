@@ -39,6 +39,8 @@ Keep responses concise and practical. Match the user's tone.
 When given a note for context, reference it naturally.`,
   includeNoteContext: true,
   saveChatHistory: false,
+  semanticSearch: false,
+  embeddingModel: 'nomic-embed-text',
 };
 
 export const DEFAULT_DATA_SETTINGS: DataSettings = {
@@ -46,7 +48,10 @@ export const DEFAULT_DATA_SETTINGS: DataSettings = {
   backupEnabled: true,
   backupFrequency: 'daily',
   backupRetention: 7,
+  backupWeeklyRetention: 4,
+  backupDirectory: null,
   exportFormat: 'markdown',
+  trashRetentionDays: 30,
 };
 
 export const DEFAULT_NOTIFICATION_SETTINGS: NotificationSettings = {
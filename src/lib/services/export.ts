@@ -1,10 +1,21 @@
-import { save } from '@tauri-apps/plugin-dialog';
+import { invoke } from '@tauri-apps/api/core';
+import { open, save } from '@tauri-apps/plugin-dialog';
 import { writeTextFile } from '@tauri-apps/plugin-fs';
 import type { Note } from '../types';
 import { formatDateDisplay } from '../utils/date';
 
 export type ExportFormat = 'markdown' | 'json';
 
+export type JournalExportFormat = 'markdown' | 'html' | 'zip';
+
+export interface JournalExportSummary {
+  path: string;
+  notes: number;
+  days: number;
+  // Notes left out of Markdown/zip exports because their date is invalid
+  skipped: number;
+}
+
 interface ExportOptions {
   notes: Note[];
   format: ExportFormat;
@@ -130,3 +141,34 @@ export async function backupAllData(notes: Note[]): Promise<boolean> {
     throw error;
   }
 }
+
+/**
+ * Export the journal (optionally limited to a date range) from the backend,
+ * which reads straight from the database instead of notes loaded in the UI.
+ */
+export async function exportJournal(
+  format: JournalExportFormat,
+  fromDate?: string,
+  toDate?: string
+): Promise<JournalExportSummary | null> {
+  const today = new Date().toISOString().split('T')[0];
+
+  const path =
+    format === 'markdown'
+      ? await open({ directory: true, title: 'Choose export folder' })
+      : await save({
+          defaultPath: `helper-journal-${today}.${format}`,
+          filters:
+            format === 'html'
+              ? [{ name: 'HTML', extensions: ['html'] }]
+              : [{ name: 'Zip Archive', extensions: ['zip'] }],
+        });
+
+  if (!path || Array.isArray(path)) {
+    return null; // User cancelled
+  }
+
+  return await invoke<JournalExportSummary>('export_notes', {
+    options: { format, path, fromDate: fromDate ?? null, toDate: toDate ?? null },
+  });
+}
@@ -1,24 +1,75 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, Channel } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import type { Note, ChatMessage, OllamaStatus } from '../types';
+import type {
+  Note,
+  ChatMessage,
+  ChatOutcome,
+  ChatStats,
+  ChatStreamEvent,
+  JournalAnswer,
+  OllamaStatus,
+  OllamaModel,
+  OllamaModelDescription,
+  PullProgress,
+  MergeResult,
+  DatabaseStatus,
+  AppError,
+  Template,
+  SemanticResults,
+  JournalContext,
+} from '../types';
 import { addNote, navigateToToday } from '../stores/notes';
 import { toggleSettings } from '../stores/ui';
 import { createNote } from '../utils/note';
 import { currentDate } from '../stores/notes';
 import { get } from 'svelte/store';
 
+export function isAppError(error: unknown): error is AppError {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    typeof (error as AppError).code === 'string' &&
+    typeof (error as AppError).message === 'string'
+  );
+}
+
 // Custom error class for Tauri operations
 export class TauriServiceError extends Error {
+  // Structured details when the backend returned an AppError
+  public code?: AppError['code'];
+  public category?: AppError['category'];
+  public retryable: boolean;
+
   constructor(
     public operation: string,
     public originalError: unknown
   ) {
-    const message = originalError instanceof Error ? originalError.message : String(originalError);
+    const message =
+      originalError instanceof Error
+        ? originalError.message
+        : isAppError(originalError)
+          ? originalError.message
+          : String(originalError);
     super(`${operation}: ${message}`);
     this.name = 'TauriServiceError';
+    this.code = isAppError(originalError) ? originalError.code : undefined;
+    this.category = isAppError(originalError) ? originalError.category : undefined;
+    this.retryable = isAppError(originalError) ? originalError.retryable : false;
+  }
+}
+
+// Thrown when a note changed in the database since it was loaded
+export class NoteConflictError extends Error {
+  constructor(public current: Note) {
+    super(`Note ${current.id} was changed elsewhere`);
+    this.name = 'NoteConflictError';
   }
 }
 
+function isConflict(error: unknown): error is AppError & { details: { current: Note } } {
+  return isAppError(error) && error.code === 'conflict' && error.details !== undefined;
+}
+
 // Note operations
 export async function fetchNotesForDate(date: string): Promise<Note[]> {
   try {
@@ -40,16 +91,31 @@ export async function saveNote(note: Note): Promise<Note | null> {
   }
 }
 
-export async function updateNoteInDb(note: Note): Promise<Note | null> {
+/**
+ * Save changes to a note. Pass the `updatedAt` the edit was based on to
+ * detect concurrent changes; a conflict throws NoteConflictError.
+ */
+export async function updateNoteInDb(note: Note, expectedUpdatedAt?: string): Promise<Note | null> {
   try {
-    return await invoke<Note>('update_note', { note });
+    return await invoke<Note>('update_note', { note, expectedUpdatedAt });
   } catch (error) {
+    if (isConflict(error)) {
+      throw new NoteConflictError(error.details.current);
+    }
     const tauriError = new TauriServiceError('Failed to update note', error);
     console.error(tauriError.message, { noteId: note.id, originalError: error });
     return null;
   }
 }
 
+export async function mergeNoteContent(
+  base: string,
+  yours: string,
+  saved: string
+): Promise<MergeResult> {
+  return invoke<MergeResult>('merge_note_content', { base, yours, saved });
+}
+
 export async function deleteNoteFromDb(id: string, deletedAt: string): Promise<boolean> {
   try {
     await invoke('delete_note', { id, deletedAt });
@@ -61,6 +127,57 @@ export async function deleteNoteFromDb(id: string, deletedAt: string): Promise<b
   }
 }
 
+// Semantic search
+export async function semanticSearch(query: string, limit?: number): Promise<SemanticResults> {
+  try {
+    return await invoke<SemanticResults>('semantic_search', { query, limit });
+  } catch (error) {
+    throw new TauriServiceError('Semantic search failed', error);
+  }
+}
+
+// Templates
+export async function listTemplates(): Promise<Template[]> {
+  return invoke<Template[]>('list_templates');
+}
+
+export async function createTemplate(
+  name: string,
+  content: string,
+  defaultWeekdays: string[] = []
+): Promise<Template> {
+  return invoke<Template>('create_template', { name, content, defaultWeekdays });
+}
+
+export async function updateTemplate(template: Template): Promise<Template> {
+  return invoke<Template>('update_template', { template });
+}
+
+export async function deleteTemplate(id: string): Promise<void> {
+  await invoke('delete_template', { id });
+}
+
+/**
+ * Render a template for a note on `date`. Without a context the backend
+ * looks up the current weather itself when weather is enabled.
+ */
+export async function applyTemplate(
+  id: string,
+  date: string,
+  context?: JournalContext
+): Promise<string> {
+  return invoke<string>('apply_template', { id, date, context });
+}
+
+// Database startup
+export async function getDatabaseStatus(): Promise<DatabaseStatus> {
+  return invoke<DatabaseStatus>('get_database_status');
+}
+
+export async function retryDatabaseInitialization(): Promise<DatabaseStatus> {
+  return invoke<DatabaseStatus>('retry_database_initialization');
+}
+
 // Database health check
 export async function checkDatabaseHealth(): Promise<boolean> {
   try {
@@ -87,28 +204,152 @@ export async function checkOllamaStatus(url: string): Promise<OllamaStatus> {
   }
 }
 
+/**
+ * Send a chat message to Ollama. Failures throw a TauriServiceError whose
+ * `code` tells e.g. an offline Ollama apart from a missing model. Passing a
+ * `requestId` allows cancelChatRequest to stop it, which resolves with a
+ * cancelled outcome.
+ */
 export async function sendChatMessage(
   url: string,
   model: string,
   messages: ChatMessage[],
   temperature: number,
-  maxTokens: number
-): Promise<ChatMessage | null> {
+  maxTokens: number,
+  requestId?: string
+): Promise<ChatOutcome> {
   try {
-    return await invoke<ChatMessage>('send_chat_message', {
+    return await invoke<ChatOutcome>('send_chat_message', {
       url,
       model,
       messages,
       temperature,
       maxTokens,
+      requestId,
     });
   } catch (error) {
     const tauriError = new TauriServiceError('Failed to send chat message', error);
     console.error(tauriError.message, { url, model, messageCount: messages.length, originalError: error });
-    return null;
+    throw tauriError;
   }
 }
 
+export interface StreamChatOptions {
+  // Lets cancelChatRequest stop the reply
+  requestId?: string;
+  onDone?: (stats: ChatStats) => void;
+}
+
+/**
+ * Send a chat message to Ollama and receive the reply as it is generated.
+ * `onChunk` gets each new piece of text; the outcome holds the complete
+ * message once the stream ends. Failures throw a TauriServiceError like
+ * sendChatMessage.
+ */
+export async function streamChatMessage(
+  url: string,
+  model: string,
+  messages: ChatMessage[],
+  temperature: number,
+  maxTokens: number,
+  onChunk: (content: string) => void,
+  options: StreamChatOptions = {}
+): Promise<ChatOutcome> {
+  const onEvent = new Channel<ChatStreamEvent>();
+  onEvent.onmessage = (message) => {
+    if (message.event === 'chunk') {
+      onChunk(message.data.content);
+    } else {
+      options.onDone?.(message.data.stats);
+    }
+  };
+
+  try {
+    return await invoke<ChatOutcome>('stream_chat_message', {
+      url,
+      model,
+      messages,
+      temperature,
+      maxTokens,
+      requestId: options.requestId,
+      onEvent,
+    });
+  } catch (error) {
+    const tauriError = new TauriServiceError('Failed to send chat message', error);
+    console.error(tauriError.message, { url, model, messageCount: messages.length, originalError: error });
+    throw tauriError;
+  }
+}
+
+/**
+ * Ask a question about the journal. The backend finds the relevant entries,
+ * streams an answer citing them as [n], and resolves with the entries used.
+ * `history` is the earlier conversation, without the question.
+ */
+export async function askJournal(
+  question: string,
+  history: ChatMessage[],
+  onChunk: (content: string) => void,
+  options: StreamChatOptions = {}
+): Promise<JournalAnswer> {
+  const onEvent = new Channel<ChatStreamEvent>();
+  onEvent.onmessage = (message) => {
+    if (message.event === 'chunk') {
+      onChunk(message.data.content);
+    } else {
+      options.onDone?.(message.data.stats);
+    }
+  };
+
+  try {
+    return await invoke<JournalAnswer>('ask_journal', {
+      question,
+      history,
+      requestId: options.requestId,
+      onEvent,
+    });
+  } catch (error) {
+    throw new TauriServiceError('Failed to answer from the journal', error);
+  }
+}
+
+// Stop a chat request; returns false if it had already finished
+export async function cancelChatRequest(requestId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_chat_request', { requestId });
+}
+
+// Ollama model management
+export async function listOllamaModels(url: string): Promise<OllamaModel[]> {
+  return invoke<OllamaModel[]>('list_ollama_models', { url });
+}
+
+export async function showOllamaModel(url: string, model: string): Promise<OllamaModelDescription> {
+  return invoke<OllamaModelDescription>('show_ollama_model', { url, model });
+}
+
+/**
+ * Download a model into Ollama. `onProgress` gets each step Ollama reports;
+ * resolves once the model is installed.
+ */
+export async function pullOllamaModel(
+  url: string,
+  model: string,
+  onProgress: (progress: PullProgress) => void
+): Promise<void> {
+  const onEvent = new Channel<PullProgress>();
+  onEvent.onmessage = onProgress;
+
+  try {
+    await invoke('pull_ollama_model', { url, model, onProgress: onEvent });
+  } catch (error) {
+    throw new TauriServiceError(`Failed to download ${model}`, error);
+  }
+}
+
+export async function deleteOllamaModel(url: string, model: string): Promise<void> {
+  await invoke('delete_ollama_model', { url, model });
+}
+
 // Event listeners for tray actions
 export function setupTrayListeners(): () => void {
   const unlisteners: (() => void)[] = [];
@@ -1,6 +1,6 @@
 import { writable, derived } from 'svelte/store';
 import type { ChatMessage, OllamaStatus } from '../types';
-import { checkOllamaStatus } from '../services/tauri';
+import { checkOllamaStatus, pullOllamaModel } from '../services/tauri';
 
 // Chat panel visibility
 export const chatPanelOpen = writable<boolean>(false);
@@ -24,6 +24,15 @@ export const ollamaStatus = writable<OllamaStatus>({
 // Whether we're currently checking/retrying Ollama connection
 export const ollamaChecking = writable<boolean>(false);
 
+// A model download in progress; percent is null while Ollama reports no size
+export interface ModelDownload {
+  model: string;
+  status: string;
+  percent: number | null;
+}
+
+export const modelDownload = writable<ModelDownload | null>(null);
+
 // Toggle chat panel
 export function toggleChatPanel(): void {
   chatPanelOpen.update((open) => !open);
@@ -34,6 +43,20 @@ export function addChatMessage(message: ChatMessage): void {
   chatMessages.update((messages) => [...messages, message]);
 }
 
+// Append streamed text to the last message, i.e. the reply being generated
+export function appendToLastChatMessage(content: string): void {
+  chatMessages.update((messages) => {
+    const last = messages[messages.length - 1];
+    if (!last) return messages;
+    return [...messages.slice(0, -1), { ...last, content: last.content + content }];
+  });
+}
+
+// Replace the last message, e.g. a streamed reply with its final version
+export function replaceLastChatMessage(message: ChatMessage): void {
+  chatMessages.update((messages) => [...messages.slice(0, -1), message]);
+}
+
 // Clear chat history
 export function clearChat(): void {
   chatMessages.set([]);
@@ -76,5 +99,22 @@ export async function refreshOllamaStatus(ollamaUrl: string): Promise<boolean> {
   }
 }
 
+// Download a model into Ollama, tracking progress in modelDownload. Errors
+// are left to the caller to report.
+export async function downloadModel(ollamaUrl: string, model: string): Promise<void> {
+  modelDownload.set({ model, status: 'Starting download', percent: null });
+  try {
+    await pullOllamaModel(ollamaUrl, model, (progress) => {
+      const percent = progress.total
+        ? Math.floor(((progress.completed ?? 0) / progress.total) * 100)
+        : null;
+      modelDownload.set({ model, status: progress.status, percent });
+    });
+    await refreshOllamaStatus(ollamaUrl);
+  } finally {
+    modelDownload.set(null);
+  }
+}
+
 // Derived: is chat available
 export const isChatAvailable = derived(ollamaStatus, ($status) => $status.connected);
@@ -0,0 +1,44 @@
+import { writable } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+import type { DatabaseStatus } from '../types';
+import { getDatabaseStatus, retryDatabaseInitialization } from '../services/tauri';
+
+// Whether the database has opened; commands wait for it while initializing
+export const databaseStatus = writable<DatabaseStatus>({ state: 'initializing' });
+
+/**
+ * Track the database state. Events keep the store current; the initial
+ * query covers events emitted before the listeners were registered.
+ */
+export function setupDatabaseListeners(): () => void {
+  const unlisteners: (() => void)[] = [];
+
+  listen('database-ready', () => {
+    databaseStatus.set({ state: 'ready' });
+  }).then((unlisten) => unlisteners.push(unlisten));
+
+  listen('database-locked', () => {
+    databaseStatus.set({ state: 'locked' });
+  }).then((unlisten) => unlisteners.push(unlisten));
+
+  listen<string>('database-failed', (event) => {
+    databaseStatus.set({ state: 'failed', message: event.payload });
+  }).then((unlisten) => unlisteners.push(unlisten));
+
+  getDatabaseStatus()
+    .then((status) => databaseStatus.set(status))
+    .catch((error) => console.error('Failed to get database status', error));
+
+  return () => {
+    unlisteners.forEach((unlisten) => unlisten());
+  };
+}
+
+export async function retryDatabase(): Promise<void> {
+  databaseStatus.set({ state: 'initializing' });
+  try {
+    databaseStatus.set(await retryDatabaseInitialization());
+  } catch (error) {
+    databaseStatus.set({ state: 'failed', message: String(error) });
+  }
+}
@@ -4,3 +4,4 @@ export * from './settings';
 export * from './chat';
 export * from './ui';
 export * from './stt';
+export * from './database';
@@ -1,7 +1,15 @@
 import { writable, derived, get } from 'svelte/store';
 import type { Note } from '../types';
 import { formatDateISO, getTimestamp } from '../utils/date';
-import { fetchNotesForDate, saveNote, updateNoteInDb, deleteNoteFromDb } from '../services/tauri';
+import {
+  fetchNotesForDate,
+  saveNote,
+  updateNoteInDb,
+  deleteNoteFromDb,
+  mergeNoteContent,
+  NoteConflictError,
+} from '../services/tauri';
+import { extractTitle } from '../utils/note';
 
 // Current date being viewed
 export const currentDate = writable<string>(formatDateISO(new Date()));
@@ -71,6 +79,9 @@ export async function addNote(note: Note): Promise<void> {
       return new Map(map);
     });
     selectedNoteId.set(previousNotes.length > 0 ? previousNotes[0].id : null);
+  } else if (saved.content !== note.content) {
+    // A blank note was filled in from the weekday's default template
+    replaceNote(saved);
   }
 }
 
@@ -89,8 +100,15 @@ export async function updateNote(updatedNote: Note): Promise<void> {
     return new Map(map);
   });
 
-  // Persist to database
-  const saved = await updateNoteInDb(updatedNote);
+  // Persist to database, only if nobody else changed the note in the meantime
+  let saved: Note | null;
+  try {
+    saved = await updateNoteInDb(updatedNote, previousNote?.updatedAt);
+  } catch (error) {
+    if (!(error instanceof NoteConflictError) || !previousNote) throw error;
+    saved = await resolveConflict(previousNote, updatedNote, error.current);
+  }
+
   if (!saved) {
     console.error('Failed to update note in database');
     // Revert optimistic update
@@ -103,6 +121,46 @@ export async function updateNote(updatedNote: Note): Promise<void> {
   }
 }
 
+function replaceNote(note: Note): void {
+  notesMap.update((map) => {
+    const dateNotes = map.get(note.date) || [];
+    map.set(
+      note.date,
+      dateNotes.map((n) => (n.id === note.id ? note : n))
+    );
+    return new Map(map);
+  });
+}
+
+/**
+ * Merge an edit with the version saved by someone else and save the result.
+ * Overlapping changes end up between conflict markers in the note for the
+ * user to resolve, so neither side's text is lost.
+ */
+async function resolveConflict(base: Note, edited: Note, current: Note): Promise<Note | null> {
+  const merge = await mergeNoteContent(base.content, edited.content, current.content);
+  const merged: Note = {
+    ...edited,
+    content: merge.content,
+    title: extractTitle(merge.content),
+    updatedAt: getTimestamp(),
+  };
+  if (!merge.clean) {
+    console.warn(`Note ${edited.id} had ${merge.conflicts} conflicting change(s)`);
+  }
+
+  try {
+    const saved = await updateNoteInDb(merged, current.updatedAt);
+    if (saved) replaceNote(merged);
+    return saved;
+  } catch (error) {
+    if (!(error instanceof NoteConflictError)) throw error;
+    // Still changing underneath us; show what is saved rather than loop
+    replaceNote(error.current);
+    return error.current;
+  }
+}
+
 export async function deleteNote(noteId: string, date: string): Promise<void> {
   // Store previous state for potential revert
   const previousNotes = get(notesMap).get(date) || [];
@@ -1,4 +1,5 @@
 import { writable, derived, get } from 'svelte/store';
+import { invoke } from '@tauri-apps/api/core';
 import { load, Store } from '@tauri-apps/plugin-store';
 import type { Settings, Theme } from '../types';
 import {
@@ -29,22 +30,32 @@ export const settings = writable<Settings>({
   natLangChain: { ...DEFAULT_NATLANGCHAIN_SETTINGS },
 });
 
+// Merge saved settings with defaults (in case new settings were added)
+function withDefaults(saved: Partial<Settings>): Settings {
+  return {
+    app: { ...DEFAULT_APP_SETTINGS, ...saved.app },
+    ai: { ...DEFAULT_AI_SETTINGS, ...saved.ai },
+    data: { ...DEFAULT_DATA_SETTINGS, ...saved.data },
+    notifications: { ...DEFAULT_NOTIFICATION_SETTINGS, ...saved.notifications },
+    weather: { ...DEFAULT_WEATHER_SETTINGS, ...saved.weather },
+    natLangChain: { ...DEFAULT_NATLANGCHAIN_SETTINGS, ...saved.natLangChain },
+  };
+}
+
 // Initialize persistent store and load settings
 export async function initializeSettings(): Promise<void> {
   try {
     persistentStore = await load(SETTINGS_STORE_PATH);
-    const savedSettings = await persistentStore.get<Settings>(SETTINGS_KEY);
-
-    if (savedSettings) {
-      // Merge saved settings with defaults (in case new settings were added)
-      settings.set({
-        app: { ...DEFAULT_APP_SETTINGS, ...savedSettings.app },
-        ai: { ...DEFAULT_AI_SETTINGS, ...savedSettings.ai },
-        data: { ...DEFAULT_DATA_SETTINGS, ...savedSettings.data },
-        notifications: { ...DEFAULT_NOTIFICATION_SETTINGS, ...savedSettings.notifications },
-        weather: { ...DEFAULT_WEATHER_SETTINGS, ...savedSettings.weather },
-        natLangChain: { ...DEFAULT_NATLANGCHAIN_SETTINGS, ...savedSettings.natLangChain },
-      });
+
+    // The backend is the source of truth; the store file is only a fallback
+    // for when the database is not available yet (e.g. still locked)
+    try {
+      settings.set(withDefaults(await invoke<Settings>('get_settings')));
+    } catch {
+      const savedSettings = await persistentStore.get<Settings>(SETTINGS_KEY);
+      if (savedSettings) {
+        settings.set(withDefaults(savedSettings));
+      }
     }
 
     settingsLoaded.set(true);
@@ -54,12 +65,19 @@ export async function initializeSettings(): Promise<void> {
   }
 }
 
-// Save current settings to disk
+// Save current settings to the backend, keeping the store file in sync as a fallback
 async function saveSettings(): Promise<void> {
+  const currentSettings = get(settings);
+
+  try {
+    await invoke<Settings>('update_settings', { settings: currentSettings });
+  } catch (error) {
+    console.error('Failed to save settings:', error);
+  }
+
   if (!persistentStore) return;
 
   try {
-    const currentSettings = get(settings);
     await persistentStore.set(SETTINGS_KEY, currentSettings);
     await persistentStore.save();
   } catch (error) {
@@ -7,6 +7,70 @@ export interface Note {
   createdAt: string; // ISO 8601
   updatedAt: string; // ISO 8601
   deletedAt?: string; // Soft delete
+  tags?: string[];
+  context?: JournalContext | null; // Weather/time snapshot from when the note was written
+}
+
+// Database startup state, mirrored from the backend
+export interface DatabaseStatus {
+  state: 'initializing' | 'ready' | 'locked' | 'failed';
+  message?: string; // Set when state is 'failed'
+}
+
+// Error returned by every backend command
+export interface AppError {
+  code: AppErrorCode;
+  category: 'network' | 'auth' | 'validation' | 'storage' | 'not-found' | 'conflict' | 'internal';
+  retryable: boolean; // Whether trying again later may succeed
+  message: string; // Fit to show the user
+  details?: { current: Note }; // Set when code is 'conflict'
+}
+
+export type AppErrorCode =
+  | 'database_unavailable'
+  | 'database_locked'
+  | 'database_busy'
+  | 'wrong_passphrase'
+  | 'storage'
+  | 'invalid_input'
+  | 'not_found'
+  | 'conflict'
+  | 'ollama_offline'
+  | 'model_not_found'
+  | 'network'
+  | 'timeout'
+  | 'unauthorized'
+  | 'service'
+  | 'internal';
+
+// Result of merging a conflicting edit with the saved version of a note
+export interface Template {
+  id: string;
+  name: string;
+  // Markdown with {{date}}, {{weekday}}, {{weather}}, {{moon_phase}} and {{time_of_day}} placeholders
+  content: string;
+  // Lowercase weekday names on which new notes start from this template
+  defaultWeekdays: string[];
+  createdAt: string;
+  updatedAt: string;
+}
+
+export interface SemanticHit {
+  note: Note;
+  // Cosine similarity to the query; higher is a better match
+  score: number;
+}
+
+export interface SemanticResults {
+  hits: SemanticHit[];
+  // Notes not yet indexed with the current embedding model
+  pending: number;
+}
+
+export interface MergeResult {
+  content: string; // Conflicting regions are wrapped in <<<<<<< / ======= / >>>>>>> markers
+  clean: boolean;
+  conflicts: number;
 }
 
 // Chat types
@@ -16,6 +80,34 @@ export interface ChatMessage {
   timestamp: string;
 }
 
+// Generation stats Ollama reports once a streamed reply is complete
+export interface ChatStats {
+  promptEvalCount?: number;
+  evalCount?: number;
+  totalDurationMs?: number;
+  evalDurationMs?: number;
+}
+
+// How a chat request ended; cancelling is not an error
+export type ChatOutcome =
+  | { outcome: 'completed'; message: ChatMessage }
+  | { outcome: 'cancelled' };
+
+// A journal entry a journal answer was based on
+export interface JournalSource {
+  number: number; // As cited in the answer, e.g. [2]
+  noteId: string;
+  date: string;
+  title?: string | null;
+  cited: boolean;
+}
+
+export type JournalAnswer = ChatOutcome & { sources: JournalSource[] };
+
+export type ChatStreamEvent =
+  | { event: 'chunk'; data: { content: string } }
+  | { event: 'done'; data: { message: ChatMessage; stats: ChatStats } };
+
 export interface ChatHistory {
   id: string;
   noteId?: string;
@@ -30,6 +122,44 @@ export interface OllamaStatus {
   error: string | null;
 }
 
+export interface OllamaModelDetails {
+  format?: string;
+  family?: string;
+  families?: string[];
+  parameterSize?: string;
+  quantizationLevel?: string;
+}
+
+// An installed Ollama model
+export interface OllamaModel {
+  name: string;
+  // Size on disk in bytes
+  size: number;
+  digest: string;
+  modifiedAt: string;
+  details: OllamaModelDetails;
+}
+
+export interface OllamaModelDescription {
+  name: string;
+  details: OllamaModelDetails;
+  contextLength?: number;
+  capabilities: string[];
+  license?: string;
+  parameters?: string;
+  template?: string;
+  system?: string;
+  modifiedAt?: string;
+}
+
+// One step of a model download; sizes in bytes while a layer downloads
+export interface PullProgress {
+  status: string;
+  digest?: string;
+  total?: number;
+  completed?: number;
+}
+
 export interface OllamaRequest {
   model: string;
   messages: ChatMessage[];
@@ -75,6 +205,10 @@ export interface AISettings {
   systemPrompt: string;
   includeNoteContext: boolean;
   saveChatHistory: boolean;
+  // Embed notes in the background so they can be found by meaning
+  semanticSearch: boolean;
+  // Changing it re-embeds every note
+  embeddingModel: string;
 }
 
 export interface DataSettings {
@@ -82,7 +216,10 @@ export interface DataSettings {
   backupEnabled: boolean;
   backupFrequency: BackupFrequency;
   backupRetention: number;
+  backupWeeklyRetention: number;
+  backupDirectory: string | null; // Absolute path; null uses the app data folder
   exportFormat: ExportFormat;
+  trashRetentionDays: number; // 0 keeps trashed notes forever
 }
 
 export interface NotificationSettings {
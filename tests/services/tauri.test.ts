@@ -5,9 +5,17 @@ import {
   fetchNotesForDate,
   saveNote,
   updateNoteInDb,
+  mergeNoteContent,
+  NoteConflictError,
   deleteNoteFromDb,
   checkOllamaStatus,
   sendChatMessage,
+  streamChatMessage,
+  askJournal,
+  cancelChatRequest,
+  listOllamaModels,
+  semanticSearch,
+  TauriServiceError,
 } from '../../src/lib/services/tauri';
 import type { Note, ChatMessage } from '../../src/lib/types';
 
@@ -96,6 +104,63 @@ describe('Tauri Service', () => {
       expect(mockInvoke).toHaveBeenCalledWith('update_note', { note });
       expect(result).toEqual(note);
     });
+
+    it('should send the version the edit was based on', async () => {
+      const note = { id: '1', content: 'Edited' } as Note;
+      mockInvoke.mockResolvedValueOnce(note);
+
+      await updateNoteInDb(note, '2025-12-26T10:00:00.000Z');
+
+      expect(mockInvoke).toHaveBeenCalledWith('update_note', {
+        note,
+        expectedUpdatedAt: '2025-12-26T10:00:00.000Z',
+      });
+    });
+
+    it('should throw NoteConflictError with the saved version on conflict', async () => {
+      const current = { id: '1', content: 'Changed elsewhere' } as Note;
+      mockInvoke.mockRejectedValueOnce({
+        code: 'conflict',
+        category: 'conflict',
+        retryable: false,
+        message: 'Note was changed since it was opened',
+        details: { current },
+      });
+
+      const error = await updateNoteInDb({ id: '1' } as Note, 'stale').catch((e) => e);
+
+      expect(error).toBeInstanceOf(NoteConflictError);
+      expect(error.current).toEqual(current);
+    });
+
+    it('should return null on other failures', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'not_found',
+        category: 'not-found',
+        retryable: false,
+        message: 'Note not found: 1',
+      });
+
+      const result = await updateNoteInDb({ id: '1' } as Note);
+
+      expect(result).toBeNull();
+    });
+  });
+
+  describe('mergeNoteContent', () => {
+    it('should invoke merge_note_content', async () => {
+      const merged = { content: 'a\nb\n', clean: true, conflicts: 0 };
+      mockInvoke.mockResolvedValueOnce(merged);
+
+      const result = await mergeNoteContent('a\n', 'a\nb\n', 'a\n');
+
+      expect(mockInvoke).toHaveBeenCalledWith('merge_note_content', {
+        base: 'a\n',
+        yours: 'a\nb\n',
+        saved: 'a\n',
+      });
+      expect(result).toEqual(merged);
+    });
   });
 
   describe('deleteNoteFromDb', () => {
@@ -161,14 +226,15 @@ describe('Tauri Service', () => {
         timestamp: new Date().toISOString(),
       };
 
-      mockInvoke.mockResolvedValueOnce(mockResponse);
+      mockInvoke.mockResolvedValueOnce({ outcome: 'completed', message: mockResponse });
 
       const response = await sendChatMessage(
         'http://localhost:11434',
         'llama3.2:3b',
         messages,
         0.7,
-        500
+        500,
+        'request-1'
       );
 
       expect(mockInvoke).toHaveBeenCalledWith('send_chat_message', {
@@ -177,22 +243,230 @@ describe('Tauri Service', () => {
         messages,
         temperature: 0.7,
         maxTokens: 500,
+        requestId: 'request-1',
       });
-      expect(response).toEqual(mockResponse);
+      expect(response).toEqual({ outcome: 'completed', message: mockResponse });
     });
 
-    it('should return null on error', async () => {
-      mockInvoke.mockRejectedValueOnce(new Error('Model not found'));
+    it('should resolve with a cancelled outcome when the request is stopped', async () => {
+      mockInvoke.mockResolvedValueOnce({ outcome: 'cancelled' });
 
-      const response = await sendChatMessage(
+      const response = await sendChatMessage('http://localhost:11434', 'llama3.2:3b', [], 0.7, 500, 'request-1');
+
+      expect(response.outcome).toBe('cancelled');
+    });
+
+    it('should throw the structured backend error', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'model_not_found',
+        category: 'not-found',
+        retryable: false,
+        message: "Model 'invalid-model' is not installed in Ollama",
+      });
+
+      const error = await sendChatMessage('http://localhost:11434', 'invalid-model', [], 0.7, 500).catch(
+        (e) => e
+      );
+
+      expect(error).toBeInstanceOf(TauriServiceError);
+      expect(error.code).toBe('model_not_found');
+    });
+  });
+
+  describe('streamChatMessage', () => {
+    it('should pass each chunk and the final stats to the callbacks', async () => {
+      const reply: ChatMessage = {
+        role: 'assistant',
+        content: 'Hi there!',
+        timestamp: new Date().toISOString(),
+      };
+
+      mockInvoke.mockImplementationOnce(async (_command, args) => {
+        const { onEvent } = args as { onEvent: { onmessage: (event: unknown) => void } };
+        onEvent.onmessage({ event: 'chunk', data: { content: 'Hi ' } });
+        onEvent.onmessage({ event: 'chunk', data: { content: 'there!' } });
+        onEvent.onmessage({ event: 'done', data: { message: reply, stats: { evalCount: 3 } } });
+        return { outcome: 'completed', message: reply };
+      });
+
+      const chunks: string[] = [];
+      const onDone = vi.fn();
+      const response = await streamChatMessage(
         'http://localhost:11434',
-        'invalid-model',
+        'llama3.2:3b',
         [],
         0.7,
-        500
+        500,
+        (content) => chunks.push(content),
+        { requestId: 'request-1', onDone }
+      );
+
+      expect(mockInvoke).toHaveBeenCalledWith(
+        'stream_chat_message',
+        expect.objectContaining({ model: 'llama3.2:3b', maxTokens: 500, requestId: 'request-1' })
+      );
+      expect(chunks).toEqual(['Hi ', 'there!']);
+      expect(onDone).toHaveBeenCalledWith({ evalCount: 3 });
+      expect(response).toEqual({ outcome: 'completed', message: reply });
+    });
+
+    it('should throw the structured backend error', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'timeout',
+        category: 'network',
+        retryable: true,
+        message: 'Ollama sent nothing for 120 seconds',
+      });
+
+      const error = await streamChatMessage('http://localhost:11434', 'llama3.2:3b', [], 0.7, 500, () => {}).catch(
+        (e) => e
+      );
+
+      expect(error).toBeInstanceOf(TauriServiceError);
+      expect(error.retryable).toBe(true);
+    });
+  });
+
+  describe('askJournal', () => {
+    it('should stream the answer and return the entries it drew on', async () => {
+      const reply: ChatMessage = {
+        role: 'assistant',
+        content: 'You went hiking with Sam [1].',
+        timestamp: new Date().toISOString(),
+      };
+      const sources = [
+        { number: 1, noteId: 'note-1', date: '2026-10-06', title: 'Hike', cited: true },
+        { number: 2, noteId: 'note-2', date: '2026-10-09', title: null, cited: false },
+      ];
+
+      mockInvoke.mockImplementationOnce(async (_command, args) => {
+        const { onEvent } = args as { onEvent: { onmessage: (event: unknown) => void } };
+        onEvent.onmessage({ event: 'chunk', data: { content: reply.content } });
+        onEvent.onmessage({ event: 'done', data: { message: reply, stats: {} } });
+        return { outcome: 'completed', message: reply, sources };
+      });
+
+      const chunks: string[] = [];
+      const answer = await askJournal(
+        'What did I do last week?',
+        [],
+        (content) => chunks.push(content),
+        { requestId: 'request-2' }
+      );
+
+      expect(mockInvoke).toHaveBeenCalledWith(
+        'ask_journal',
+        expect.objectContaining({
+          question: 'What did I do last week?',
+          history: [],
+          requestId: 'request-2',
+        })
       );
+      expect(chunks).toEqual([reply.content]);
+      expect(answer).toEqual({ outcome: 'completed', message: reply, sources });
+    });
+
+    it('should throw when the question cannot be answered', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'ollama_offline',
+        category: 'network',
+        retryable: true,
+        message: 'Cannot connect to Ollama',
+      });
+
+      const error = await askJournal('Anything?', [], () => {}).catch((e) => e);
+
+      expect(error).toBeInstanceOf(TauriServiceError);
+      expect(error.code).toBe('ollama_offline');
+    });
+  });
+
+  describe('cancelChatRequest', () => {
+    it('should cancel the request by id', async () => {
+      mockInvoke.mockResolvedValueOnce(true);
+
+      const cancelled = await cancelChatRequest('request-1');
+
+      expect(mockInvoke).toHaveBeenCalledWith('cancel_chat_request', { requestId: 'request-1' });
+      expect(cancelled).toBe(true);
+    });
+  });
+
+  describe('semanticSearch', () => {
+    it('should return the best matches and the number of notes still to index', async () => {
+      const note: Note = {
+        id: 'note-1',
+        date: '2026-03-14',
+        title: 'Lake walk',
+        content: 'Quiet morning by the water',
+        createdAt: '2026-03-14T08:00:00.000Z',
+        updatedAt: '2026-03-14T08:00:00.000Z',
+      };
+      const results = { hits: [{ note, score: 0.82 }], pending: 3 };
+      mockInvoke.mockResolvedValueOnce(results);
+
+      const found = await semanticSearch('days I felt calm', 5);
+
+      expect(mockInvoke).toHaveBeenCalledWith('semantic_search', { query: 'days I felt calm', limit: 5 });
+      expect(found).toEqual(results);
+    });
+
+    it('should throw the structured backend error', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'invalid_input',
+        category: 'validation',
+        retryable: false,
+        message: 'Semantic search is turned off in settings',
+      });
+
+      const error = await semanticSearch('calm').catch((e) => e);
+
+      expect(error).toBeInstanceOf(TauriServiceError);
+      expect(error.code).toBe('invalid_input');
+    });
+  });
+
+  describe('listOllamaModels', () => {
+    it('should return the installed models', async () => {
+      const models = [
+        {
+          name: 'llama3.2:3b',
+          size: 2019393189,
+          digest: 'a80c4f17acd5',
+          modifiedAt: '2026-01-01T00:00:00Z',
+          details: { family: 'llama', parameterSize: '3.2B', quantizationLevel: 'Q4_K_M' },
+        },
+      ];
+      mockInvoke.mockResolvedValueOnce(models);
+
+      const result = await listOllamaModels('http://localhost:11434');
+
+      expect(mockInvoke).toHaveBeenCalledWith('list_ollama_models', { url: 'http://localhost:11434' });
+      expect(result).toEqual(models);
+    });
+  });
+
+  describe('TauriServiceError', () => {
+    it('should carry the code and retryable flag of a backend error', () => {
+      const error = new TauriServiceError('Failed to send chat message', {
+        code: 'ollama_offline',
+        category: 'network',
+        retryable: true,
+        message: 'Ollama is not running at http://localhost:11434',
+      });
+
+      expect(error.message).toBe('Failed to send chat message: Ollama is not running at http://localhost:11434');
+      expect(error.code).toBe('ollama_offline');
+      expect(error.category).toBe('network');
+      expect(error.retryable).toBe(true);
+    });
+
+    it('should fall back to the plain message for other errors', () => {
+      const error = new TauriServiceError('Failed to save note', 'boom');
 
-      expect(response).toBeNull();
+      expect(error.message).toBe('Failed to save note: boom');
+      expect(error.code).toBeUndefined();
+      expect(error.retryable).toBe(false);
     });
   });
 });
@@ -3,6 +3,9 @@ import { vi } from 'vitest';
 // Mock Tauri APIs
 vi.mock('@tauri-apps/api/core', () => ({
   invoke: vi.fn(),
+  Channel: class<T> {
+    onmessage: (message: T) => void = () => {};
+  },
 }));
 
 vi.mock('@tauri-apps/api/event', () => ({
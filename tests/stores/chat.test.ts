@@ -1,5 +1,6 @@
-import { describe, it, expect, beforeEach } from 'vitest';
+import { describe, it, expect, beforeEach, vi } from 'vitest';
 import { get } from 'svelte/store';
+import { invoke } from '@tauri-apps/api/core';
 import {
   chatPanelOpen,
   chatMessages,
@@ -12,6 +13,9 @@ import {
   setOllamaConnected,
   setOllamaDisconnected,
   isChatAvailable,
+  appendToLastChatMessage,
+  modelDownload,
+  downloadModel,
 } from '../../src/lib/stores/chat';
 import type { ChatMessage } from '../../src/lib/types';
 
@@ -140,4 +144,42 @@ describe('Chat Store', () => {
       expect(get(chatLoading)).toBe(false);
     });
   });
+
+  describe('appendToLastChatMessage', () => {
+    it('should extend the reply being streamed', () => {
+      addChatMessage({ role: 'assistant', content: 'Hel', timestamp: '2026-01-01T00:00:00.000Z' });
+
+      appendToLastChatMessage('lo');
+
+      expect(get(chatMessages)[0].content).toBe('Hello');
+    });
+  });
+
+  describe('downloadModel', () => {
+    it('should track progress and refresh the status when done', async () => {
+      const seen: (number | null)[] = [];
+      const unsubscribe = modelDownload.subscribe((download) => {
+        if (download) seen.push(download.percent);
+      });
+
+      vi.mocked(invoke).mockImplementation(async (command, args) => {
+        if (command === 'pull_ollama_model') {
+          const { onProgress } = args as { onProgress: { onmessage: (p: unknown) => void } };
+          onProgress.onmessage({ status: 'pulling manifest' });
+          onProgress.onmessage({ status: 'pulling abc', total: 200, completed: 50 });
+          onProgress.onmessage({ status: 'success' });
+          return null;
+        }
+        return { connected: true, model: 'llama3.2:3b', error: null };
+      });
+
+      await downloadModel('http://localhost:11434', 'llama3.2:3b');
+      unsubscribe();
+      vi.mocked(invoke).mockReset();
+
+      expect(seen).toContain(25);
+      expect(get(modelDownload)).toBeNull();
+      expect(get(ollamaStatus).connected).toBe(true);
+    });
+  });
 });
@@ -0,0 +1,66 @@
+import { describe, it, expect, beforeEach, vi } from 'vitest';
+import { get } from 'svelte/store';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import {
+  databaseStatus,
+  setupDatabaseListeners,
+  retryDatabase,
+} from '../../src/lib/stores/database';
+
+// Mocks are set up in setup.ts
+const mockInvoke = invoke as ReturnType<typeof vi.fn>;
+const mockListen = listen as ReturnType<typeof vi.fn>;
+
+describe('Database Store', () => {
+  beforeEach(() => {
+    vi.clearAllMocks();
+    databaseStatus.set({ state: 'initializing' });
+  });
+
+  describe('setupDatabaseListeners', () => {
+    it('should pick up a status reported before the listeners existed', async () => {
+      mockInvoke.mockResolvedValueOnce({ state: 'ready' });
+
+      const cleanup = setupDatabaseListeners();
+      await vi.waitFor(() => expect(get(databaseStatus).state).toBe('ready'));
+
+      expect(mockInvoke).toHaveBeenCalledWith('get_database_status');
+      cleanup();
+    });
+
+    it('should track the failure message from database-failed', async () => {
+      mockInvoke.mockResolvedValueOnce({ state: 'initializing' });
+      const handlers: Record<string, (event: { payload: unknown }) => void> = {};
+      mockListen.mockImplementation((name: string, handler) => {
+        handlers[name] = handler;
+        return Promise.resolve(() => {});
+      });
+
+      setupDatabaseListeners();
+      handlers['database-failed']({ payload: 'disk I/O error' });
+
+      expect(get(databaseStatus)).toEqual({ state: 'failed', message: 'disk I/O error' });
+    });
+  });
+
+  describe('retryDatabase', () => {
+    it('should store the status returned by the retry', async () => {
+      databaseStatus.set({ state: 'failed', message: 'locked file' });
+      mockInvoke.mockResolvedValueOnce({ state: 'ready' });
+
+      await retryDatabase();
+
+      expect(mockInvoke).toHaveBeenCalledWith('retry_database_initialization');
+      expect(get(databaseStatus)).toEqual({ state: 'ready' });
+    });
+
+    it('should stay failed when the retry is rejected', async () => {
+      mockInvoke.mockRejectedValueOnce('Database is not in a failed state');
+
+      await retryDatabase();
+
+      expect(get(databaseStatus).state).toBe('failed');
+    });
+  });
+});
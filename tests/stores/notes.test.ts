@@ -14,6 +14,7 @@ import {
   navigateNextDay,
   navigateToToday,
 } from '../../src/lib/stores/notes';
+import { invoke } from '@tauri-apps/api/core';
 import type { Note } from '../../src/lib/types';
 
 describe('Notes Store', () => {
@@ -139,6 +140,22 @@ describe('Notes Store', () => {
 
       expect(get(currentNotes)).toHaveLength(2);
     });
+
+    it('should show the content filled in from a default template', async () => {
+      const note: Note = {
+        id: 'templated',
+        date: get(currentDate),
+        title: '',
+        content: '',
+        createdAt: new Date().toISOString(),
+        updatedAt: new Date().toISOString(),
+      };
+      vi.mocked(invoke).mockResolvedValueOnce({ ...note, content: '# Gratitude\n\n- ' });
+
+      await addNote(note);
+
+      expect(get(currentNotes)[0].content).toBe('# Gratitude\n\n- ');
+    });
   });
 
   describe('updateNote', () => {
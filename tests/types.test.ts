@@ -223,6 +223,8 @@ describe('Type Definitions', () => {
           systemPrompt: 'You are HeLpER...',
           includeNoteContext: true,
           saveChatHistory: false,
+          semanticSearch: false,
+          embeddingModel: 'nomic-embed-text',
         },
         data: {
           dataLocation: '',